@@ -0,0 +1,20 @@
+#![feature(async_fn_in_trait)]
+#![allow(incomplete_features)]
+
+use itsdns::*;
+
+#[tokio::main]
+async fn main() {
+    let stack = std_embedded_nal_async::Stack::default();
+    let client = ItsDns::from_system(stack).unwrap();
+    println!("Using servers {:?}", client.servers());
+
+    let host = std::env::args().nth(1).unwrap_or_else(|| "example.com".into());
+    println!("Resolving {}...", host);
+    let addrs = client
+        .get_host_addrs(&host, embedded_nal_async::AddrType::Either)
+        .await
+        .unwrap();
+
+    println!("Resolved {} to {:?}", host, addrs);
+}
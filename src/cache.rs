@@ -0,0 +1,141 @@
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embedded_nal_async::{AddrType, IpAddr};
+use heapless::Vec;
+
+use crate::hosts::matches_type;
+use crate::message::Domain;
+use crate::name::DomainName;
+
+/// Maximum number of cached addresses.
+pub const MAX_CACHE: usize = 8;
+
+#[derive(Clone, Debug)]
+struct Entry {
+    name: DomainName,
+    addr: IpAddr,
+    expires_ms: u64,
+}
+
+/// A fixed size cache of addresses from answers, kept until their TTL expires.
+/// When full, the entry closest to expiring is replaced.
+pub(crate) struct Cache {
+    size: usize,
+    entries: Mutex<RefCell<Vec<Entry, MAX_CACHE>>>,
+}
+
+impl Cache {
+    pub(crate) const fn new(size: usize) -> Self {
+        Self {
+            size,
+            entries: Mutex::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Find all addresses of `name` that have not expired at `now_ms`.
+    pub(crate) fn get<const N: usize>(
+        &self,
+        name: &Domain<'_>,
+        addr_type: &AddrType,
+        now_ms: u64,
+    ) -> Vec<IpAddr, N> {
+        let mut addrs = Vec::new();
+        if self.size == 0 {
+            return addrs;
+        }
+        critical_section::with(|cs| {
+            let mut entries = self.entries.borrow_ref_mut(cs);
+            entries.retain(|e| e.expires_ms > now_ms);
+            for e in entries
+                .iter()
+                .filter(|e| e.name == *name && matches_type(&e.addr, addr_type))
+            {
+                addrs.push(e.addr).ok();
+            }
+        });
+        addrs
+    }
+
+    /// Cache `addr` for `name` for `ttl` seconds from `now_ms`.
+    pub(crate) fn insert(&self, name: &Domain<'_>, addr: IpAddr, ttl: u32, now_ms: u64) {
+        if self.size == 0 || ttl == 0 {
+            return;
+        }
+        let name = match DomainName::from_domain(name) {
+            Ok(name) => name,
+            Err(_) => return,
+        };
+        let entry = Entry {
+            name,
+            addr,
+            expires_ms: now_ms + ttl as u64 * 1000,
+        };
+        critical_section::with(|cs| {
+            let mut entries = self.entries.borrow_ref_mut(cs);
+            if let Some(e) = entries
+                .iter_mut()
+                .find(|e| e.name == entry.name && e.addr == entry.addr)
+            {
+                *e = entry;
+            } else if entries.len() < self.size {
+                // The size is at most the capacity
+                entries.push(entry).ok();
+            } else if let Some(e) = entries.iter_mut().min_by_key(|e| e.expires_ms) {
+                *e = entry;
+            }
+        })
+    }
+
+    /// Remove all entries.
+    pub(crate) fn clear(&self) {
+        critical_section::with(|cs| self.entries.borrow_ref_mut(cs).clear())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_nal_async::{Ipv4Addr, Ipv6Addr};
+
+    fn get(cache: &Cache, name: &str, addr_type: AddrType, now_ms: u64) -> Option<IpAddr> {
+        let addrs = cache.get::<1>(&Domain::String(name), &addr_type, now_ms);
+        addrs.first().copied()
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = Cache::new(2);
+        let a = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let b = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+        let c = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        let name = Domain::String("a.example.com");
+
+        cache.insert(&name, a, 60, 1000);
+        assert_eq!(Some(a), get(&cache, "A.example.com.", AddrType::IPv4, 2000));
+        assert_eq!(None, get(&cache, "a.example.com", AddrType::IPv6, 2000));
+        assert_eq!(None, get(&cache, "a.example.com", AddrType::Either, 61000));
+
+        // Replaces the entry closest to expiring when full
+        cache.insert(&name, a, 10, 0);
+        cache.insert(&Domain::String("b.example.com"), b, 20, 0);
+        cache.insert(&Domain::String("c.example.com"), c, 30, 0);
+        assert_eq!(None, get(&cache, "a.example.com", AddrType::Either, 0));
+        assert_eq!(Some(b), get(&cache, "b.example.com", AddrType::Either, 0));
+        assert_eq!(Some(c), get(&cache, "c.example.com", AddrType::IPv6, 0));
+
+        cache.clear();
+        assert_eq!(None, get(&cache, "b.example.com", AddrType::Either, 0));
+
+        // All addresses of a name
+        let cache = Cache::new(2);
+        cache.insert(&name, a, 60, 0);
+        cache.insert(&name, c, 60, 0);
+        let all: Vec<IpAddr, 2> = cache.get(&name, &AddrType::Either, 0);
+        assert_eq!([a, c], *all);
+
+        let disabled = Cache::new(0);
+        disabled.insert(&name, a, 60, 0);
+        assert_eq!(None, get(&disabled, "a.example.com", AddrType::Either, 0));
+    }
+}
@@ -0,0 +1,226 @@
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::task::Poll;
+
+/// A source of time, used for query timeouts and cache expiry.
+pub trait Clock {
+    /// Milliseconds since an arbitrary point in time, never going backwards.
+    fn now_ms(&self) -> u64;
+
+    /// Wait for `ms` milliseconds.
+    async fn delay_ms(&self, ms: u32);
+}
+
+/// A clock for stacks without a timer.
+///
+/// Time stands still and delays never end, so queries wait for a response
+/// without a timeout. It should only be used with caching disabled, as cached
+/// entries would never expire.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoClock;
+
+impl Clock for NoClock {
+    fn now_ms(&self) -> u64 {
+        0
+    }
+
+    async fn delay_ms(&self, _ms: u32) {
+        core::future::pending().await
+    }
+}
+
+/// Run `fut` to completion, or give up with `None` after `ms` milliseconds.
+pub(crate) async fn timeout<C: Clock, F: Future>(clock: &C, ms: u32, fut: F) -> Option<F::Output> {
+    let mut fut = pin!(fut);
+    let mut delay = pin!(clock.delay_ms(ms));
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = fut.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        if delay.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// A clock using the operating system's monotonic time.
+///
+/// Delays are woken by a timer thread shared by all clocks, started on first
+/// use, so that the clock works with any executor. A delay dropped before it
+/// ends, such as the timeout of a query that was answered, is cancelled.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct StdClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl StdClock {
+    /// Create a clock counting from now.
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now_ms(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    async fn delay_ms(&self, ms: u32) {
+        Delay {
+            deadline: std::time::Instant::now() + std::time::Duration::from_millis(ms as u64),
+            id: None,
+        }
+        .await
+    }
+}
+
+/// The pending delays of all [`StdClock`]s.
+#[cfg(feature = "std")]
+struct Timers {
+    /// The deadline and id of each delay, with the task to wake when it ends,
+    /// or `None` once woken.
+    pending: std::vec::Vec<(std::time::Instant, u64, Option<core::task::Waker>)>,
+    next_id: u64,
+    started: bool,
+}
+
+#[cfg(feature = "std")]
+static TIMERS: std::sync::Mutex<Timers> = std::sync::Mutex::new(Timers {
+    pending: std::vec::Vec::new(),
+    next_id: 0,
+    started: false,
+});
+
+/// Notified when a delay is added, so that the timer thread sleeps until the
+/// earliest deadline.
+#[cfg(feature = "std")]
+static TIMERS_CHANGED: std::sync::Condvar = std::sync::Condvar::new();
+
+/// A delay of a [`StdClock`], registered with the timer thread when first
+/// polled and removed when dropped.
+#[cfg(feature = "std")]
+struct Delay {
+    deadline: std::time::Instant,
+    id: Option<u64>,
+}
+
+#[cfg(feature = "std")]
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: core::pin::Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if std::time::Instant::now() >= this.deadline {
+            return Poll::Ready(());
+        }
+        let mut timers = TIMERS.lock().unwrap();
+        let waker = Some(cx.waker().clone());
+        match this.id {
+            Some(id) => {
+                if let Some(delay) = timers.pending.iter_mut().find(|d| d.1 == id) {
+                    delay.2 = waker;
+                }
+            }
+            None => {
+                let id = timers.next_id;
+                timers.next_id += 1;
+                timers.pending.push((this.deadline, id, waker));
+                this.id = Some(id);
+                if !timers.started {
+                    timers.started = true;
+                    std::thread::spawn(run_timers);
+                }
+                TIMERS_CHANGED.notify_one();
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for Delay {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            TIMERS.lock().unwrap().pending.retain(|d| d.1 != id);
+        }
+    }
+}
+
+/// Wake the delays that ended, and sleep until the next one ends or another
+/// is added.
+#[cfg(feature = "std")]
+fn run_timers() {
+    let mut timers = TIMERS.lock().unwrap();
+    loop {
+        let now = std::time::Instant::now();
+        let mut ended = std::vec::Vec::new();
+        for (deadline, _, waker) in timers.pending.iter_mut() {
+            if *deadline <= now {
+                ended.extend(waker.take());
+            }
+        }
+        if !ended.is_empty() {
+            // Woken without the lock, as the tasks may poll their delays
+            drop(timers);
+            ended.into_iter().for_each(core::task::Waker::wake);
+            timers = TIMERS.lock().unwrap();
+            continue;
+        }
+        let next = timers
+            .pending
+            .iter()
+            .filter(|d| d.2.is_some())
+            .map(|d| d.0)
+            .min();
+        timers = match next {
+            Some(deadline) => {
+                let wait = deadline.saturating_duration_since(now);
+                TIMERS_CHANGED.wait_timeout(timers, wait).unwrap().0
+            }
+            None => TIMERS_CHANGED.wait(timers).unwrap(),
+        };
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_std_clock() {
+        let clock = StdClock::new();
+        clock.delay_ms(20).await;
+        assert!(clock.now_ms() >= 20);
+
+        assert_eq!(None, timeout(&clock, 10, clock.delay_ms(60_000)).await);
+        assert_eq!(Some(()), timeout(&clock, 60_000, clock.delay_ms(10)).await);
+
+        // A dropped delay is cancelled. Other tests share the timers, so only
+        // this delay is looked for.
+        let mut delay = Delay {
+            deadline: std::time::Instant::now() + std::time::Duration::from_secs(60),
+            id: None,
+        };
+        let poll = poll_fn(|cx| Poll::Ready(core::pin::Pin::new(&mut delay).poll(cx))).await;
+        assert_eq!(Poll::Pending, poll);
+        let id = delay.id.unwrap();
+        let pending = |id| TIMERS.lock().unwrap().pending.iter().any(|d| d.1 == id);
+        assert!(pending(id));
+        drop(delay);
+        assert!(!pending(id));
+    }
+}
@@ -0,0 +1,213 @@
+use embedded_nal_async::SocketAddr;
+
+use crate::search::DEFAULT_NDOTS;
+use crate::MAX_CACHE;
+
+/// Maximum number of servers.
+pub const MAX_SERVERS: usize = 4;
+
+/// Maximum number of buffers for [`Strategy::Race`], enough for the AAAA and A
+/// queries of a lookup to race with every server at once.
+pub const MAX_RACE_BUFFERS: usize = 2 * (MAX_SERVERS - 1);
+
+/// Largest UDP payload size that can be advertised with EDNS, as recommended to
+/// avoid IP fragmentation. Responses are received into a buffer of this size.
+pub const MAX_UDP_PAYLOAD: u16 = 1232;
+
+/// Where query ids come from, along with the randomness for ordering SRV
+/// targets.
+#[derive(Debug, Clone, Copy)]
+pub enum IdSource {
+    /// Ids counting up from 0. Predictable, so only suitable on trusted networks.
+    /// SRV targets are then ordered as if the ids were random.
+    Sequential,
+    /// Ids from a random number generator.
+    Random(fn() -> u16),
+}
+
+/// How queries are sent to the servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// One server at a time, trying the next one after a failure or timeout.
+    Sequential,
+    /// Several servers at a time, which helps on lossy links. Up to `fanout`
+    /// queries are in flight, started `stagger_ms` apart, and the next server is
+    /// queried as soon as one fails. The first valid response wins and the other
+    /// queries are cancelled.
+    ///
+    /// The responses of all but one query are received into the buffers given to
+    /// [`ItsDns::set_race_buffers`](crate::ItsDns::set_race_buffers). Racing
+    /// falls back to one query in flight, as with [`Strategy::Sequential`],
+    /// when those buffers were not given or are all in use by other lookups.
+    Race {
+        /// The number of queries in flight, from 1 to [`MAX_SERVERS`].
+        fanout: u8,
+        /// The delay before starting each further query, or 0 to start them
+        /// all at once.
+        stagger_ms: u32,
+    },
+}
+
+/// What queries are sent over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// UDP only. A response too large for a datagram fails with
+    /// [`Error::Truncated`](crate::Error::Truncated), unless
+    /// [`Config::accept_truncated`] is set.
+    Udp,
+    /// UDP, asking again over TCP (RFC 7766) when a response is truncated, with
+    /// the TCP stack given to [`ItsDns::with_tcp`](crate::ItsDns::with_tcp).
+    ///
+    /// The response over TCP is received into the same buffer, of
+    /// [`MAX_UDP_PAYLOAD`] bytes, so larger ones are still truncated. When the
+    /// query over TCP fails, for instance without a TCP stack, the result is
+    /// [`Error::Truncated`](crate::Error::Truncated).
+    UdpThenTcp,
+}
+
+/// Configuration of an [`ItsDns`](crate::ItsDns) client.
+///
+/// All settings have defaults and are set with builder methods, which can be
+/// used in a `const` context:
+///
+/// ```
+/// use embedded_nal_async::{IpAddr, Ipv4Addr, SocketAddr};
+/// use itsdns::Config;
+///
+/// static SERVERS: [SocketAddr; 2] = [
+///     SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)), 53),
+///     SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
+/// ];
+/// static CONFIG: Config = Config::new()
+///     .servers(&SERVERS)
+///     .search(&["site1.example.net"])
+///     .timeout_ms(1000)
+///     .edns_payload_size(Some(1232));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Config<'a> {
+    pub(crate) servers: &'a [SocketAddr],
+    pub(crate) search: &'a [&'a str],
+    pub(crate) ndots: u8,
+    pub(crate) timeout_ms: u32,
+    pub(crate) attempts: u8,
+    pub(crate) edns_payload_size: Option<u16>,
+    pub(crate) cache_size: usize,
+    pub(crate) id_source: IdSource,
+    pub(crate) accept_truncated: bool,
+    pub(crate) strategy: Strategy,
+    pub(crate) transport: Transport,
+}
+
+impl<'a> Config<'a> {
+    /// The default configuration, which has no servers.
+    ///
+    /// **The query ids of the default [`IdSource::Sequential`] are predictable**,
+    /// which makes it easy for anyone on the path to the servers, or able to
+    /// send packets to the client, to forge responses and poison the cache.
+    /// Unless the network is trusted, set [`IdSource::Random`] with
+    /// [`id_source`](Self::id_source).
+    pub const fn new() -> Self {
+        Self {
+            servers: &[],
+            search: &[],
+            ndots: DEFAULT_NDOTS,
+            timeout_ms: 2000,
+            attempts: 2,
+            edns_payload_size: None,
+            cache_size: MAX_CACHE,
+            id_source: IdSource::Sequential,
+            accept_truncated: false,
+            strategy: Strategy::Sequential,
+            transport: Transport::Udp,
+        }
+    }
+
+    /// The servers to query, in order of preference. At most [`MAX_SERVERS`].
+    pub const fn servers(mut self, servers: &'a [SocketAddr]) -> Self {
+        self.servers = servers;
+        self
+    }
+
+    /// The domains appended to names that are not absolute, tried in order.
+    /// At most [`MAX_SEARCH`](crate::MAX_SEARCH).
+    pub const fn search(mut self, search: &'a [&'a str]) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// The number of dots a name needs to be tried as is before the search
+    /// domains. Defaults to 1.
+    pub const fn ndots(mut self, ndots: u8) -> Self {
+        self.ndots = ndots;
+        self
+    }
+
+    /// How long to wait for a response from a server before trying the next.
+    /// Defaults to 2 seconds.
+    pub const fn timeout_ms(mut self, timeout_ms: u32) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// How many times to go through the server list before giving up.
+    /// Defaults to 2.
+    pub const fn attempts(mut self, attempts: u8) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// The UDP payload size to advertise with EDNS (RFC 6891), between 512 and
+    /// [`MAX_UDP_PAYLOAD`], or `None` to send queries without EDNS. Defaults to
+    /// `None`.
+    pub const fn edns_payload_size(mut self, size: Option<u16>) -> Self {
+        self.edns_payload_size = size;
+        self
+    }
+
+    /// The number of addresses to cache, at most [`MAX_CACHE`]. Set to 0 to
+    /// disable the cache. Defaults to [`MAX_CACHE`].
+    pub const fn cache_size(mut self, size: usize) -> Self {
+        self.cache_size = size;
+        self
+    }
+
+    /// Where query ids come from. Defaults to [`IdSource::Sequential`], which is
+    /// only safe on trusted networks.
+    pub const fn id_source(mut self, id_source: IdSource) -> Self {
+        self.id_source = id_source;
+        self
+    }
+
+    /// Use the records of truncated responses instead of failing with
+    /// [`Error::Truncated`](crate::Error::Truncated). With [`Transport::Udp`],
+    /// this is the only way to use answers that do not fit in a datagram, and
+    /// with [`Transport::UdpThenTcp`] it only applies to responses over TCP.
+    /// Defaults to `false`.
+    pub const fn accept_truncated(mut self, accept: bool) -> Self {
+        self.accept_truncated = accept;
+        self
+    }
+
+    /// How queries are sent to the servers. Defaults to [`Strategy::Sequential`].
+    ///
+    /// Racing receives each response in flight into a buffer of its own, which
+    /// need to be given with
+    /// [`ItsDns::set_race_buffers`](crate::ItsDns::set_race_buffers).
+    pub const fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// What queries are sent over. Defaults to [`Transport::Udp`].
+    pub const fn transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+}
+
+impl<'a> Default for Config<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,134 @@
+use embedded_nal_async::{AddrType, SocketAddr, TcpConnect, UdpStack};
+use heapless::Vec;
+
+use crate::clock::timeout;
+use crate::{AttemptError, Clock, ConnectError, ItsDns, NoTcp};
+
+/// Connects to hosts by name over a TCP stack, resolving them with an
+/// [`ItsDns`] client.
+///
+/// The addresses of a name are tried one at a time, in the order given by
+/// [`ItsDns::get_host_addrs`], until a connection is established. Each attempt
+/// is given up after a timeout, which needs the client to have a [`Clock`].
+pub struct Connector<'d, T, S: UdpStack, C: Clock, D: TcpConnect = NoTcp> {
+    tcp: T,
+    dns: &'d ItsDns<S, C, D>,
+    addr_type: AddrType,
+    timeout_ms: u32,
+}
+
+impl<'d, T: TcpConnect, S: UdpStack, C: Clock, D: TcpConnect> Connector<'d, T, S, C, D> {
+    /// Create a connector using the TCP stack and the DNS client, connecting to
+    /// addresses of both families with a timeout of 5 seconds per attempt.
+    pub fn new(tcp: T, dns: &'d ItsDns<S, C, D>) -> Self {
+        Self {
+            tcp,
+            dns,
+            addr_type: AddrType::Either,
+            timeout_ms: 5000,
+        }
+    }
+
+    /// Only connect to addresses of this family, for instance on a stack
+    /// without IPv6.
+    pub fn addr_type(mut self, addr_type: AddrType) -> Self {
+        self.addr_type = addr_type;
+        self
+    }
+
+    /// How long to wait for each connection attempt.
+    pub fn timeout_ms(mut self, timeout_ms: u32) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Connect to `port` on `host`, a name or an address literal.
+    pub async fn connect<'a>(
+        &'a self,
+        host: &str,
+        port: u16,
+    ) -> Result<T::Connection<'a>, ConnectError<S::Error, T::Error>> {
+        let addrs = self
+            .dns
+            .get_host_addrs(host, self.addr_type.clone())
+            .await
+            .map_err(ConnectError::Resolve)?;
+        let mut attempts = Vec::new();
+        for ip in addrs {
+            let remote = SocketAddr::new(ip, port);
+            let connect = self.tcp.connect(remote);
+            let error = match timeout(&self.dns.clock, self.timeout_ms, connect).await {
+                Some(Ok(connection)) => return Ok(connection),
+                Some(Err(e)) => AttemptError::Network(e),
+                None => AttemptError::Timeout,
+            };
+            // There are as many attempts as addresses
+            attempts.push((remote, error)).ok();
+        }
+        Err(ConnectError::Failed { attempts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{server, MockStack, MockTcp, Reply, Server};
+    use crate::{Config, Error, QType, Rcode};
+    extern crate std;
+    use std::string::ToString;
+    use std::vec;
+
+    #[tokio::test]
+    async fn test_connect() {
+        let stack = MockStack::new(vec![Server::new(1, |q| match q.qtype {
+            _ if q.qname.to_string() == "missing.example.com" => Reply::Rcode(Rcode::NXDOMAIN),
+            QType::AAAA => Reply::Records(vec![(
+                QType::AAAA,
+                vec![0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            )]),
+            _ => Reply::Records(vec![
+                (QType::A, vec![192, 0, 2, 1]),
+                (QType::A, vec![192, 0, 2, 2]),
+            ]),
+        })]);
+        let servers = [server(1)];
+        let config = Config::new().servers(&servers);
+        let dns = ItsDns::with_config(&stack, stack.clock.clone(), &config).unwrap();
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+
+        let tcp = MockTcp {
+            accept: vec![addr("192.0.2.2:80")],
+            refuse: vec![addr("192.0.2.1:80")],
+            ..Default::default()
+        };
+        let connector = Connector::new(&tcp, &dns).timeout_ms(100);
+        let connection = connector.connect("www.example.com", 80).await.unwrap();
+        assert_eq!(addr("192.0.2.2:80"), connection.0);
+        assert_eq!(
+            [
+                addr("[2001:db8::1]:80"),
+                addr("192.0.2.1:80"),
+                addr("192.0.2.2:80")
+            ],
+            tcp.attempts.borrow()[..]
+        );
+
+        let tcp = MockTcp::default();
+        let connector = Connector::new(&tcp, &dns)
+            .addr_type(AddrType::IPv4)
+            .timeout_ms(100);
+        match connector.connect("www.example.com", 443).await {
+            Err(e @ ConnectError::Failed { .. }) => assert_eq!(
+                "could not connect: 192.0.2.1:443: timed out, 192.0.2.2:443: timed out",
+                e.to_string()
+            ),
+            _ => panic!("connected"),
+        }
+
+        let result = connector.connect("missing.example.com", 443).await;
+        assert!(matches!(
+            result,
+            Err(ConnectError::Resolve(Error::NxDomain { .. }))
+        ));
+    }
+}
@@ -0,0 +1,414 @@
+use core::fmt;
+use embedded_nal_async::SocketAddr;
+use heapless::Vec;
+
+use crate::MAX_ADDRS;
+
+/// Errors returned by the client.
+#[derive(Debug)]
+pub enum Error<N> {
+    /// Error in the underlying network
+    Network(N),
+    /// Error specific to DNS
+    Dns(DnsError),
+    /// DNS entry not found
+    NotFound,
+    /// Not a host and port, or a service name, to resolve to socket addresses.
+    InvalidAddress,
+    /// The name does not exist (NXDOMAIN).
+    NxDomain {
+        /// The server that answered.
+        server: SocketAddr,
+    },
+    /// The name exists, but has no records of the requested type (NODATA).
+    NoData {
+        /// The server that answered.
+        server: SocketAddr,
+    },
+    /// The server answered with an error response code.
+    Server {
+        /// The server that answered.
+        server: SocketAddr,
+        /// The response code, including any extended bits from EDNS.
+        rcode: Rcode,
+    },
+    /// No response arrived in time from any attempt, the last one being to `server`.
+    Timeout {
+        /// The server that did not answer.
+        server: SocketAddr,
+    },
+    /// The response was truncated and could not be used, nor asked for again
+    /// over TCP.
+    Truncated {
+        /// The server that answered.
+        server: SocketAddr,
+    },
+    /// The response does not belong to the query that was sent, having another
+    /// id or question.
+    IdMismatch {
+        /// The server that answered.
+        server: SocketAddr,
+        /// The id of the query.
+        expected: u16,
+        /// The id of the response.
+        received: u16,
+    },
+    /// The response could not be decoded.
+    Malformed {
+        /// The server that answered.
+        server: SocketAddr,
+        /// Where and why decoding failed.
+        error: DecodeError,
+    },
+}
+
+/// Errors connecting to a host by name with a [`Connector`](crate::Connector).
+#[derive(Debug)]
+// Without an allocator, the attempts can not be boxed
+#[allow(clippy::large_enum_variant)]
+pub enum ConnectError<N, T> {
+    /// The name could not be resolved.
+    Resolve(Error<N>),
+    /// None of the addresses of the name could be connected to.
+    Failed {
+        /// The addresses tried, in order, and why each failed.
+        attempts: Vec<(SocketAddr, AttemptError<T>), MAX_ADDRS>,
+    },
+}
+
+/// Why connecting to one address failed.
+#[derive(Debug)]
+pub enum AttemptError<T> {
+    /// Error in the underlying network.
+    Network(T),
+    /// The connection was not established in time.
+    Timeout,
+}
+
+/// An error related to the DNS message itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DnsError {
+    /// Error encoding the message.
+    Encode,
+    /// Error decoding the message.
+    Decode(DecodeError),
+    /// Invalid domain name.
+    InvalidDomain(DomainError),
+}
+
+/// A decoding failure, with the offset in the message where it occured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DecodeError {
+    /// Offset in the message.
+    pub offset: usize,
+    /// What was wrong at the offset.
+    pub reason: DecodeReason,
+}
+
+/// Reasons a message can not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeReason {
+    /// The message ended in the middle of a field.
+    UnexpectedEnd,
+    /// Unknown opcode in the header.
+    UnknownOpcode(u8),
+    /// A label type other than a normal label or a compression pointer.
+    InvalidLabel(u8),
+    /// A compression pointer that does not point to an earlier part of the message.
+    InvalidPointer(u16),
+    /// A name longer than 255 octets.
+    NameTooLong,
+    /// Record data that does not match its type.
+    InvalidRecord,
+}
+
+/// Reasons a domain name can not be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DomainError {
+    /// A label is longer than 63 octets.
+    LabelTooLong,
+    /// The name is longer than 255 octets.
+    NameTooLong,
+    /// The name contains an empty label, such as in `example..com`.
+    EmptyLabel,
+    /// A `\` escape is not followed by a character or three decimal digits below 256.
+    InvalidEscape,
+}
+
+/// Invalid client configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// No servers were given.
+    NoServers,
+    /// More servers than [`MAX_SERVERS`](crate::MAX_SERVERS).
+    TooManyServers,
+    /// The number of attempts is 0.
+    NoAttempts,
+    /// The cache size is larger than [`MAX_CACHE`](crate::MAX_CACHE).
+    CacheTooLarge,
+    /// The EDNS payload size is below 512 or above [`MAX_UDP_PAYLOAD`](crate::MAX_UDP_PAYLOAD).
+    InvalidPayloadSize,
+    /// More search domains than [`MAX_SEARCH`](crate::MAX_SEARCH).
+    TooManySearchDomains,
+    /// A search domain is not a valid domain name.
+    InvalidSearchDomain(DomainError),
+    /// The fanout of [`Strategy::Race`](crate::Strategy::Race) is 0 or more
+    /// than [`MAX_SERVERS`](crate::MAX_SERVERS).
+    InvalidFanout,
+}
+
+/// Reasons an entry can not be added to a [`Hosts`](crate::Hosts) table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostsError {
+    /// The table is full.
+    Full,
+    /// The name is not a valid domain name.
+    InvalidName(DomainError),
+}
+
+/// A response code, combining the 4 bits in the header with the 8 extended
+/// bits of an EDNS OPT record (RFC 6891).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rcode(u16);
+
+impl Rcode {
+    /// No error.
+    pub const NOERROR: Rcode = Rcode(0);
+    /// The server was unable to interpret the query.
+    pub const FORMERR: Rcode = Rcode(1);
+    /// The server was unable to process the query.
+    pub const SERVFAIL: Rcode = Rcode(2);
+    /// The name does not exist.
+    pub const NXDOMAIN: Rcode = Rcode(3);
+    /// The server does not support the kind of query.
+    pub const NOTIMP: Rcode = Rcode(4);
+    /// The server refused to answer.
+    pub const REFUSED: Rcode = Rcode(5);
+    /// The server does not support the EDNS version.
+    pub const BADVERS: Rcode = Rcode(16);
+
+    /// Combine the header bits and the extended bits of a response code.
+    pub const fn new(header: u8, extended: u8) -> Self {
+        Self(((extended as u16) << 4) | (header & 0xF) as u16)
+    }
+
+    /// The full 12 bit response code.
+    pub const fn value(&self) -> u16 {
+        self.0
+    }
+
+    /// The lower 4 bits, as carried in the message header.
+    pub const fn header(&self) -> u8 {
+        (self.0 & 0xF) as u8
+    }
+
+    /// The upper 8 bits, as carried in the EDNS OPT record.
+    pub const fn extended(&self) -> u8 {
+        (self.0 >> 4) as u8
+    }
+}
+
+impl fmt::Display for Rcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::NOERROR => f.write_str("NOERROR"),
+            Self::FORMERR => f.write_str("FORMERR"),
+            Self::SERVFAIL => f.write_str("SERVFAIL"),
+            Self::NXDOMAIN => f.write_str("NXDOMAIN"),
+            Self::NOTIMP => f.write_str("NOTIMP"),
+            Self::REFUSED => f.write_str("REFUSED"),
+            Self::BADVERS => f.write_str("BADVERS"),
+            Self(value) => write!(f, "RCODE{}", value),
+        }
+    }
+}
+
+impl<N: fmt::Debug> fmt::Display for Error<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "network error: {:?}", e),
+            Self::Dns(e) => e.fmt(f),
+            Self::NotFound => f.write_str("not found"),
+            Self::InvalidAddress => f.write_str("invalid host and port or service name"),
+            Self::NxDomain { server } => write!(f, "{}: name does not exist", server),
+            Self::NoData { server } => write!(f, "{}: no records of the requested type", server),
+            Self::Server { server, rcode } => write!(f, "{}: server error {}", server, rcode),
+            Self::Timeout { server } => write!(f, "{}: timed out", server),
+            Self::Truncated { server } => write!(f, "{}: truncated response", server),
+            Self::IdMismatch {
+                server,
+                expected,
+                received,
+            } => write!(
+                f,
+                "{}: response id {} does not match query id {}",
+                server, received, expected
+            ),
+            Self::Malformed { server, error } => write!(f, "{}: {}", server, error),
+        }
+    }
+}
+
+impl<N: fmt::Debug, T: fmt::Debug> fmt::Display for ConnectError<N, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resolve(e) => write!(f, "could not resolve: {}", e),
+            Self::Failed { attempts } => {
+                f.write_str("could not connect")?;
+                for (i, (addr, e)) in attempts.iter().enumerate() {
+                    let sep = if i == 0 { ":" } else { "," };
+                    write!(f, "{} {}: {}", sep, addr, e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Display for AttemptError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "network error: {:?}", e),
+            Self::Timeout => f.write_str("timed out"),
+        }
+    }
+}
+
+impl fmt::Display for DnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Encode => f.write_str("message does not fit in buffer"),
+            Self::Decode(e) => e.fmt(f),
+            Self::InvalidDomain(e) => write!(f, "invalid domain name: {}", e),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed message at byte {}: ", self.offset)?;
+        match self.reason {
+            DecodeReason::UnexpectedEnd => f.write_str("unexpected end of message"),
+            DecodeReason::UnknownOpcode(op) => write!(f, "unknown opcode {}", op),
+            DecodeReason::InvalidLabel(l) => write!(f, "invalid label type {:#04x}", l),
+            DecodeReason::InvalidPointer(p) => write!(f, "invalid compression pointer {}", p),
+            DecodeReason::NameTooLong => f.write_str("name too long"),
+            DecodeReason::InvalidRecord => f.write_str("invalid record data"),
+        }
+    }
+}
+
+impl fmt::Display for DomainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::LabelTooLong => "label longer than 63 octets",
+            Self::NameTooLong => "name longer than 255 octets",
+            Self::EmptyLabel => "empty label",
+            Self::InvalidEscape => "invalid escape",
+        })
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoServers => f.write_str("no servers"),
+            Self::TooManyServers => f.write_str("too many servers"),
+            Self::NoAttempts => f.write_str("number of attempts is 0"),
+            Self::CacheTooLarge => f.write_str("cache size too large"),
+            Self::InvalidPayloadSize => f.write_str("invalid EDNS payload size"),
+            Self::TooManySearchDomains => f.write_str("too many search domains"),
+            Self::InvalidSearchDomain(e) => write!(f, "invalid search domain: {}", e),
+            Self::InvalidFanout => f.write_str("invalid fanout"),
+        }
+    }
+}
+
+impl fmt::Display for HostsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => f.write_str("hosts table is full"),
+            Self::InvalidName(e) => write!(f, "invalid host name: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<N: defmt::Format> defmt::Format for Error<N> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        use defmt::Debug2Format;
+        match self {
+            Self::Network(e) => defmt::write!(f, "network error: {}", e),
+            Self::Dns(e) => defmt::write!(f, "{}", e),
+            Self::NotFound => defmt::write!(f, "not found"),
+            Self::InvalidAddress => defmt::write!(f, "invalid host and port or service name"),
+            Self::NxDomain { server } => {
+                defmt::write!(f, "{}: name does not exist", Debug2Format(server))
+            }
+            Self::NoData { server } => defmt::write!(
+                f,
+                "{}: no records of the requested type",
+                Debug2Format(server)
+            ),
+            Self::Server { server, rcode } => defmt::write!(
+                f,
+                "{}: server error {}",
+                Debug2Format(server),
+                rcode.value()
+            ),
+            Self::Timeout { server } => {
+                defmt::write!(f, "{}: timed out", Debug2Format(server))
+            }
+            Self::Truncated { server } => {
+                defmt::write!(f, "{}: truncated response", Debug2Format(server))
+            }
+            Self::IdMismatch {
+                server,
+                expected,
+                received,
+            } => defmt::write!(
+                f,
+                "{}: response id {} does not match query id {}",
+                Debug2Format(server),
+                received,
+                expected
+            ),
+            Self::Malformed { server, error } => {
+                defmt::write!(f, "{}: {}", Debug2Format(server), error)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<N: defmt::Format, T: defmt::Format> defmt::Format for ConnectError<N, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            Self::Resolve(e) => defmt::write!(f, "could not resolve: {}", e),
+            Self::Failed { attempts } => {
+                defmt::write!(f, "could not connect");
+                for (i, (addr, e)) in attempts.iter().enumerate() {
+                    let sep = if i == 0 { ":" } else { "," };
+                    defmt::write!(f, "{} {}: {}", sep, defmt::Debug2Format(addr), e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for AttemptError<T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            Self::Network(e) => defmt::write!(f, "network error: {}", e),
+            Self::Timeout => defmt::write!(f, "timed out"),
+        }
+    }
+}
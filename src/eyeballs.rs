@@ -0,0 +1,135 @@
+use core::cmp::Reverse;
+
+use embedded_nal_async::{IpAddr, Ipv6Addr};
+use heapless::Vec;
+
+use crate::sort_stable_by_key;
+
+/// Maximum number of addresses returned by
+/// [`ItsDns::get_host_addrs`](crate::ItsDns::get_host_addrs).
+pub const MAX_ADDRS: usize = 8;
+
+/// How long to wait for AAAA records after A records arrived, or the other way
+/// around, as recommended by RFC 8305.
+pub const RESOLUTION_DELAY_MS: u32 = 50;
+
+/// The precedence of a destination address in the default policy table of
+/// RFC 6724, IPv4 addresses being looked up as IPv4-mapped IPv6 addresses.
+fn precedence(addr: &IpAddr) -> u8 {
+    let ip = match addr {
+        IpAddr::V4(_) => return 35,
+        IpAddr::V6(ip) => ip,
+    };
+    let s = ip.segments();
+    if *ip == Ipv6Addr::LOCALHOST {
+        50
+    } else if s[..5] == [0; 5] && s[5] == 0xffff {
+        35
+    } else if s[0] == 0x2002 {
+        30
+    } else if s[0] == 0x2001 && s[1] == 0 {
+        5
+    } else if s[0] & 0xfe00 == 0xfc00 {
+        3
+    } else if s[..6] == [0; 6] || s[0] & 0xffc0 == 0xfec0 || s[0] == 0x3ffe {
+        1
+    } else {
+        40
+    }
+}
+
+/// Order IPv6 and IPv4 addresses for connection attempts, as in RFC 8305: each
+/// family sorted by the precedence of RFC 6724, starting with the family whose
+/// best address has the highest precedence, IPv6 on a tie, and alternating the
+/// families from there. Addresses beyond [`MAX_ADDRS`] are dropped, taking
+/// equally from both families as far as they go.
+pub(crate) fn interleave<const N: usize, const M: usize>(
+    mut v6: Vec<IpAddr, N>,
+    mut v4: Vec<IpAddr, M>,
+) -> Vec<IpAddr, MAX_ADDRS> {
+    sort_stable_by_key(&mut v6, |a| Reverse(precedence(a)));
+    sort_stable_by_key(&mut v4, |a| Reverse(precedence(a)));
+    let (first, second): (&[IpAddr], &[IpAddr]) = match (v6.first(), v4.first()) {
+        (Some(a), Some(b)) if precedence(b) > precedence(a) => (&v4, &v6),
+        _ => (&v6, &v4),
+    };
+    let mut addrs = Vec::new();
+    let mut first = first.iter();
+    let mut second = second.iter();
+    loop {
+        let (a, b) = (first.next(), second.next());
+        if a.is_none() && b.is_none() {
+            return addrs;
+        }
+        for addr in a.into_iter().chain(b) {
+            if addrs.push(*addr).is_err() {
+                return addrs;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+    use embedded_nal_async::Ipv4Addr;
+
+    fn ip(s: &str) -> IpAddr {
+        match Ipv4Addr::from_str(s) {
+            Ok(ip) => IpAddr::V4(ip),
+            Err(_) => IpAddr::V6(Ipv6Addr::from_str(s).unwrap()),
+        }
+    }
+
+    fn addrs<const N: usize>(list: &[&str]) -> Vec<IpAddr, N> {
+        list.iter().map(|s| ip(s)).collect()
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(50, precedence(&ip("::1")));
+        assert_eq!(40, precedence(&ip("2a00:1450::1")));
+        assert_eq!(35, precedence(&ip("192.0.2.1")));
+        assert_eq!(35, precedence(&ip("::ffff:192.0.2.1")));
+        assert_eq!(30, precedence(&ip("2002:c000:201::1")));
+        assert_eq!(5, precedence(&ip("2001:0:4136::1")));
+        assert_eq!(40, precedence(&ip("2001:db8::1")));
+        assert_eq!(3, precedence(&ip("fd00::1")));
+        assert_eq!(1, precedence(&ip("fec0::1")));
+        assert_eq!(1, precedence(&ip("::192.0.2.1")));
+    }
+
+    #[test]
+    fn test_interleave() {
+        let v6 = addrs::<4>(&["2001:0:4136::1", "2001:db8::1", "2001:db8::2"]);
+        let v4 = addrs::<4>(&["192.0.2.1", "192.0.2.2", "192.0.2.3"]);
+        assert_eq!(
+            addrs::<8>(&[
+                "2001:db8::1",
+                "192.0.2.1",
+                "2001:db8::2",
+                "192.0.2.2",
+                "2001:0:4136::1",
+                "192.0.2.3",
+            ]),
+            interleave(v6, v4)
+        );
+
+        // Unique local IPv6 addresses come after IPv4
+        let v6 = addrs::<4>(&["fd00::1"]);
+        let v4 = addrs::<4>(&["192.0.2.1", "192.0.2.2"]);
+        assert_eq!(
+            addrs::<8>(&["192.0.2.1", "fd00::1", "192.0.2.2"]),
+            interleave(v6, v4)
+        );
+
+        let v6 = addrs::<8>(&["2001:db8::1"; 8]);
+        let v4 = addrs::<8>(&["192.0.2.1"; 8]);
+        let all = interleave(v6, v4);
+        assert_eq!(MAX_ADDRS, all.len());
+        assert_eq!(4, all.iter().filter(|a| a.is_ipv4()).count());
+
+        assert!(interleave(Vec::<IpAddr, 1>::new(), Vec::<IpAddr, 1>::new()).is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+use embedded_nal_async::SocketAddr;
+use heapless::Vec;
+
+use crate::MAX_SERVERS;
+
+/// How long a server is avoided after its first failure in a row. Each further
+/// failure doubles it, up to [`MAX_BACKOFF_MS`].
+const BACKOFF_MS: u32 = 1000;
+
+/// Longest time a failing server is avoided before it is probed again.
+const MAX_BACKOFF_MS: u32 = 64_000;
+
+/// The health of a server, as seen by the client, from
+/// [`ItsDns::server_stats`](crate::ItsDns::server_stats).
+///
+/// Servers are tried fastest first, by their smoothed round trip time. A server
+/// that fails is avoided for a while, longer after each failure in a row, and
+/// then probed again with the next query. Servers that are avoided are still
+/// tried when all others fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerStats {
+    /// The server.
+    pub server: SocketAddr,
+    /// Smoothed round trip time in milliseconds, or `None` before the first
+    /// response. A timeout counts as a round trip of the full timeout.
+    pub srtt_ms: Option<u32>,
+    /// Queries sent.
+    pub queries: u32,
+    /// Queries that failed, by timing out, a network error, an error response
+    /// code or an invalid response.
+    pub failures: u32,
+    /// Failures since the last response.
+    pub consecutive_failures: u8,
+    /// Until when the server is avoided, in milliseconds of the
+    /// [`Clock`](crate::Clock), or `None` if it is healthy.
+    pub backoff_until_ms: Option<u64>,
+}
+
+impl ServerStats {
+    pub(crate) const fn new(server: SocketAddr) -> Self {
+        Self {
+            server,
+            srtt_ms: None,
+            queries: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            backoff_until_ms: None,
+        }
+    }
+
+    /// Smooth in a round trip time sample, weighing the past by 7/8 as in BIND.
+    fn sample(&mut self, rtt_ms: u32) {
+        self.srtt_ms = Some(match self.srtt_ms {
+            Some(srtt) => ((srtt as u64 * 7 + rtt_ms as u64) / 8) as u32,
+            None => rtt_ms,
+        });
+    }
+
+    /// Record a response that took `rtt_ms`.
+    pub(crate) fn success(&mut self, rtt_ms: u32) {
+        self.queries = self.queries.saturating_add(1);
+        self.sample(rtt_ms);
+        self.consecutive_failures = 0;
+        self.backoff_until_ms = None;
+    }
+
+    /// Record a failure at `now_ms`, with the timeout if the server did not answer.
+    pub(crate) fn failure(&mut self, now_ms: u64, timeout_ms: Option<u32>) {
+        self.queries = self.queries.saturating_add(1);
+        self.failures = self.failures.saturating_add(1);
+        if let Some(timeout_ms) = timeout_ms {
+            self.sample(timeout_ms);
+        }
+        let backoff = BACKOFF_MS << self.consecutive_failures.min(6);
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.backoff_until_ms = Some(now_ms + backoff.min(MAX_BACKOFF_MS) as u64);
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for ServerStats {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "ServerStats {{ server: {}, srtt_ms: {}, queries: {}, failures: {}, consecutive_failures: {}, backoff_until_ms: {} }}",
+            defmt::Debug2Format(&self.server),
+            self.srtt_ms,
+            self.queries,
+            self.failures,
+            self.consecutive_failures,
+            self.backoff_until_ms
+        )
+    }
+}
+
+/// The servers in the order to try them at `now_ms`: servers due to be probed
+/// again, then healthy ones fastest first, with those not yet used counting as
+/// fastest, then the ones being avoided. Ties keep the configured order.
+pub(crate) fn order(stats: &[ServerStats], now_ms: u64) -> Vec<SocketAddr, MAX_SERVERS> {
+    let mut keyed: Vec<(u8, u64, usize), MAX_SERVERS> = Vec::new();
+    for (i, s) in stats.iter().enumerate() {
+        let key = match s.backoff_until_ms {
+            Some(until) if until > now_ms => (2, until),
+            Some(_) => (0, 0),
+            None => (1, s.srtt_ms.unwrap_or(0) as u64),
+        };
+        // The stats are as many as the servers
+        keyed.push((key.0, key.1, i)).ok();
+    }
+    keyed.sort_unstable();
+    keyed.iter().map(|&(_, _, i)| stats[i].server).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::server;
+
+    #[test]
+    fn test_stats() {
+        let mut stats = ServerStats::new(server(1));
+        stats.success(80);
+        stats.success(40);
+        assert_eq!(Some(75), stats.srtt_ms);
+
+        stats.failure(1000, Some(2000));
+        assert_eq!(Some(315), stats.srtt_ms);
+        assert_eq!(Some(2000), stats.backoff_until_ms);
+        stats.failure(1000, None);
+        stats.failure(1000, None);
+        assert_eq!(Some(5000), stats.backoff_until_ms);
+        for _ in 0..10 {
+            stats.failure(1000, None);
+        }
+        assert_eq!(Some(1000 + MAX_BACKOFF_MS as u64), stats.backoff_until_ms);
+        assert_eq!(13, stats.consecutive_failures);
+
+        stats.success(20);
+        assert_eq!(16, stats.queries);
+        assert_eq!(13, stats.failures);
+        assert_eq!(0, stats.consecutive_failures);
+        assert_eq!(None, stats.backoff_until_ms);
+    }
+
+    #[test]
+    fn test_order() {
+        let mut stats = [1, 2, 3, 4].map(|n| ServerStats::new(server(n)));
+        assert_eq!(
+            [server(1), server(2), server(3), server(4)],
+            *order(&stats, 0)
+        );
+
+        stats[0].failure(0, None);
+        stats[1].success(50);
+        stats[2].success(10);
+        assert_eq!(
+            [server(4), server(3), server(2), server(1)],
+            *order(&stats, 500)
+        );
+        // Probed again once the backoff is over
+        assert_eq!(
+            [server(1), server(4), server(3), server(2)],
+            *order(&stats, 1000)
+        );
+    }
+}
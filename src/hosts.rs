@@ -0,0 +1,303 @@
+use crate::message::Domain;
+use crate::name::DomainName;
+use crate::HostsError;
+use core::str::FromStr;
+use embedded_nal_async::{AddrType, IpAddr, Ipv4Addr, Ipv6Addr};
+use heapless::Vec;
+
+/// Maximum number of entries in a [`Hosts`] table. Adding or
+/// [loading](Hosts::load) more gives [`HostsError::Full`].
+pub const MAX_HOSTS: usize = 8;
+
+#[derive(Clone, Debug)]
+struct Entry {
+    name: DomainName,
+    wildcard: bool,
+    addr: IpAddr,
+}
+
+/// A static table of host names and their addresses, consulted before the
+/// network in both forward and reverse lookups.
+///
+/// `localhost` and all names below it resolve to the loopback address (RFC 6761),
+/// unless the table has an entry for them.
+#[derive(Clone, Debug, Default)]
+pub struct Hosts {
+    entries: Vec<Entry, MAX_HOSTS>,
+}
+
+/// Split a `*.` wildcard prefix off a name.
+fn split_wildcard(name: &str) -> (&str, bool) {
+    match name.strip_prefix("*.") {
+        Some(suffix) => (suffix, true),
+        None => (name, false),
+    }
+}
+
+fn parse_addr(s: &str) -> Option<IpAddr> {
+    match Ipv4Addr::from_str(s) {
+        Ok(ip) => Some(IpAddr::V4(ip)),
+        Err(_) => Ipv6Addr::from_str(s).ok().map(IpAddr::V6),
+    }
+}
+
+/// Returns true if `addr` is of the family asked for.
+pub(crate) fn matches_type(addr: &IpAddr, addr_type: &AddrType) -> bool {
+    matches!(
+        (addr, addr_type),
+        (_, AddrType::Either) | (IpAddr::V4(_), AddrType::IPv4) | (IpAddr::V6(_), AddrType::IPv6)
+    )
+}
+
+impl Hosts {
+    /// Create an empty table.
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add an entry mapping `name` to `addr`.
+    ///
+    /// A name starting with `*.`, such as `*.lab.example.com`, matches every name
+    /// below the rest of the name, but not the name itself. A name may have several
+    /// entries, for instance one IPv4 and one IPv6 address.
+    pub fn insert(&mut self, name: &str, addr: IpAddr) -> Result<(), HostsError> {
+        let (name, wildcard) = split_wildcard(name);
+        let name = DomainName::from_str(name).map_err(HostsError::InvalidName)?;
+        self.entries
+            .push(Entry {
+                name,
+                wildcard,
+                addr,
+            })
+            .map_err(|_| HostsError::Full)
+    }
+
+    /// Add the entries of a hosts file such as `/etc/hosts`, with an address
+    /// followed by one or more names on each line.
+    ///
+    /// Parsing is lenient: comments, lines without a valid address and invalid
+    /// names are skipped. Multicast addresses and the loopback names that are
+    /// built in are skipped too, to save room. Entries are added until the
+    /// table is full, which is reported with [`HostsError::Full`].
+    pub fn load(&mut self, text: &str) -> Result<(), HostsError> {
+        for line in text.lines() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            let mut words = line.split_ascii_whitespace();
+            let addr = match words.next().and_then(parse_addr) {
+                Some(addr) if !addr.is_multicast() => addr,
+                _ => continue,
+            };
+            for name in words {
+                if addr.is_loopback()
+                    && DomainName::from_str(name)
+                        .map_or(false, |n| n.is_subdomain_of(&Domain::String("localhost")))
+                {
+                    continue;
+                }
+                if let Err(HostsError::Full) = self.insert(name, addr) {
+                    return Err(HostsError::Full);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove all entries for `name`, returning true if there were any.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let (name, wildcard) = split_wildcard(name);
+        let name = Domain::String(name);
+        let len = self.entries.len();
+        self.entries
+            .retain(|e| e.wildcard != wildcard || e.name != name);
+        self.entries.len() != len
+    }
+
+    /// Remove all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Number of entries in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Find the address of `name`.
+    ///
+    /// Exact entries take precedence over wildcards, and a wildcard with a longer
+    /// suffix over a shorter one. Only entries matching `addr_type` are considered.
+    pub fn lookup(&self, name: &Domain<'_>, addr_type: &AddrType) -> Option<IpAddr> {
+        let name = DomainName::from_domain(name).ok()?;
+        let mut wildcard: Option<(usize, IpAddr)> = None;
+        for e in self
+            .entries
+            .iter()
+            .filter(|e| matches_type(&e.addr, addr_type))
+        {
+            if !e.wildcard {
+                if e.name == name {
+                    return Some(e.addr);
+                }
+            } else {
+                let count = e.name.label_count();
+                if name.label_count() > count
+                    && name.is_subdomain_of(&e.name.as_domain())
+                    && wildcard.map_or(true, |(c, _)| count > c)
+                {
+                    wildcard = Some((count, e.addr));
+                }
+            }
+        }
+        if let Some((_, addr)) = wildcard {
+            return Some(addr);
+        }
+
+        if name.is_subdomain_of(&Domain::String("localhost")) {
+            return Some(match addr_type {
+                AddrType::IPv6 => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                _ => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            });
+        }
+        None
+    }
+
+    /// Find the name of `addr`, from the first entry that is not a wildcard.
+    /// Loopback addresses are named `localhost`.
+    pub fn reverse(&self, addr: &IpAddr) -> Option<Domain<'_>> {
+        self.entries
+            .iter()
+            .find(|e| !e.wildcard && e.addr == *addr)
+            .map(|e| e.name.as_domain())
+            .or_else(|| addr.is_loopback().then_some(Domain::String("localhost")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::string::ToString;
+
+    fn lookup(hosts: &Hosts, name: &str, addr_type: AddrType) -> Option<IpAddr> {
+        hosts.lookup(&Domain::String(name), &addr_type)
+    }
+
+    #[test]
+    fn test_hosts() {
+        let broker = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5));
+        let lab = IpAddr::V4(Ipv4Addr::new(10, 0, 1, 1));
+        let rack = IpAddr::V4(Ipv4Addr::new(10, 0, 2, 1));
+        let broker6 = IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 5));
+
+        let mut hosts = Hosts::new();
+        hosts.insert("*.lab.example.com", lab).unwrap();
+        hosts.insert("*.rack1.lab.example.com", rack).unwrap();
+        hosts.insert("Broker.lab.example.com.", broker).unwrap();
+        hosts.insert("broker.lab.example.com", broker6).unwrap();
+
+        assert_eq!(
+            Some(broker),
+            lookup(&hosts, "broker.lab.example.com", AddrType::Either)
+        );
+        assert_eq!(
+            Some(broker6),
+            lookup(&hosts, "BROKER.lab.example.com", AddrType::IPv6)
+        );
+        assert_eq!(
+            Some(lab),
+            lookup(&hosts, "sensor.lab.example.com", AddrType::IPv4)
+        );
+        assert_eq!(
+            Some(rack),
+            lookup(&hosts, "a.b.rack1.lab.example.com", AddrType::Either)
+        );
+        assert_eq!(
+            None,
+            lookup(&hosts, "sensor.lab.example.com", AddrType::IPv6)
+        );
+        assert_eq!(None, lookup(&hosts, "lab.example.com", AddrType::Either));
+        assert_eq!(None, lookup(&hosts, "example.com", AddrType::Either));
+
+        assert_eq!(
+            "Broker.lab.example.com",
+            hosts.reverse(&broker).unwrap().to_string()
+        );
+        assert_eq!(None, hosts.reverse(&lab));
+
+        assert!(hosts.remove("*.lab.example.com"));
+        assert!(!hosts.remove("*.lab.example.com"));
+        assert_eq!(
+            None,
+            lookup(&hosts, "sensor.lab.example.com", AddrType::IPv4)
+        );
+        assert_eq!(3, hosts.len());
+
+        for i in 0..5 {
+            hosts
+                .insert("host", IpAddr::V4(Ipv4Addr::new(10, 0, 3, i)))
+                .unwrap();
+        }
+        assert_eq!(Err(HostsError::Full), hosts.insert("host", broker));
+    }
+
+    #[test]
+    fn test_localhost() {
+        let mut hosts = Hosts::new();
+        let v4 = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let v6 = Some(IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(v4, lookup(&hosts, "localhost", AddrType::Either));
+        assert_eq!(v4, lookup(&hosts, "LocalHost.", AddrType::IPv4));
+        assert_eq!(v6, lookup(&hosts, "app.localhost", AddrType::IPv6));
+        assert_eq!(
+            None,
+            lookup(&hosts, "localhost.example.com", AddrType::Either)
+        );
+        assert_eq!(
+            "localhost",
+            hosts
+                .reverse(&IpAddr::V6(Ipv6Addr::LOCALHOST))
+                .unwrap()
+                .to_string()
+        );
+
+        let other = IpAddr::V4(Ipv4Addr::new(127, 0, 1, 1));
+        hosts.insert("localhost", other).unwrap();
+        assert_eq!(Some(other), lookup(&hosts, "localhost", AddrType::IPv4));
+        assert_eq!(v6, lookup(&hosts, "localhost", AddrType::IPv6));
+    }
+
+    #[test]
+    fn test_load() {
+        let mut hosts = Hosts::new();
+        hosts
+            .load(
+                "# The following lines are desirable for IPv6 capable hosts\n\
+                 127.0.0.1\tlocalhost\n\
+                 ::1     localhost ip6-localhost  # loopback\n\
+                 ff02::1 ip6-allnodes\n\
+                 not-an-address broken\n\
+                 \n\
+                 10.0.0.5 broker.lab.example.com broker bad..name\n",
+            )
+            .unwrap();
+        assert_eq!(3, hosts.len());
+        let broker = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)));
+        assert_eq!(broker, lookup(&hosts, "broker", AddrType::Either));
+        assert_eq!(
+            Some(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+            lookup(&hosts, "ip6-localhost", AddrType::Either)
+        );
+        assert_eq!(None, lookup(&hosts, "ip6-allnodes", AddrType::Either));
+
+        let lines = "10.0.0.1 a b c d e f\n";
+        assert_eq!(Err(HostsError::Full), hosts.load(lines));
+        assert_eq!(MAX_HOSTS, hosts.len());
+    }
+}
@@ -1,117 +1,1415 @@
 #![no_std]
+#![cfg_attr(test, feature(test))]
 #![feature(impl_trait_projections)]
 #![allow(incomplete_features)]
 #![feature(async_fn_in_trait)]
+#![feature(pin_macro)]
 #![doc = include_str!("../README.md")]
 #![warn(missing_docs)]
 #![allow(clippy::upper_case_acronyms)]
 
+use core::cell::{Cell, RefCell};
+use core::fmt::Write;
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::str::FromStr;
 use core::sync::atomic::{AtomicU16, Ordering};
-use embedded_nal_async::{AddrType, ConnectedUdp, Dns, IpAddr, Ipv4Addr, SocketAddr, UdpStack};
+use core::task::Poll;
+use critical_section::Mutex;
+use embedded_nal_async::{
+    AddrType, ConnectedUdp, Dns, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpConnect, UdpStack,
+};
 use heapless::String;
 
+mod cache;
+use cache::Cache;
+pub use cache::MAX_CACHE;
+use hosts::matches_type;
+
+mod clock;
+use clock::timeout;
+#[cfg(feature = "std")]
+pub use clock::StdClock;
+pub use clock::{Clock, NoClock};
+
+mod connect;
+pub use connect::Connector;
+
+mod config;
+pub use config::{
+    Config, IdSource, Strategy, Transport, MAX_RACE_BUFFERS, MAX_SERVERS, MAX_UDP_PAYLOAD,
+};
+
+mod error;
+pub use error::{
+    AttemptError, ConfigError, ConnectError, DecodeError, DecodeReason, DnsError, DomainError,
+    Error, HostsError, Rcode,
+};
+
+mod eyeballs;
+pub use eyeballs::{MAX_ADDRS, RESOLUTION_DELAY_MS};
+
+mod health;
+pub use health::ServerStats;
+
+mod hosts;
+pub use hosts::{Hosts, MAX_HOSTS};
+
 mod message;
 use message::*;
+pub use message::{Answer, AnswerIter, Domain, Label, LabelBytes, Labels, QClass, QType};
 
-/// Errors returned by the client.
-#[derive(Debug)]
-pub enum Error<N> {
-    /// Error in the underlying network
-    Network(N),
-    /// Error specific to DNS
-    Dns(DnsError),
-    /// DNS entry not found
-    NotFound,
-}
-
-/// An error related to the DNS message itself.
-#[derive(Debug)]
-pub enum DnsError {
-    /// Error encoding the message.
-    Encode,
-    /// Error decoding the message.
-    Decode,
-    /// Message format error.
-    FormatError,
-    /// Failure occured on server.
-    ServerFailure,
-    /// Error in name.
-    NameError,
-    /// Functionality not supported.
-    NotImplemented,
-    /// Request refused.
-    Refused,
-}
+mod mx;
+pub use mx::{MailExchange, MAX_MX};
+
+mod name;
+pub use name::DomainName;
+
+mod netconfig;
+pub use netconfig::{
+    NetworkConfig, DHCP_DNS_SERVERS, DHCP_DOMAIN_NAME, DHCP_DOMAIN_SEARCH, RA_DNSSL, RA_RDNSS,
+};
+
+mod ns;
+pub use ns::{NameServer, MAX_NS};
+
+mod rdata;
+pub use rdata::{Mx, Ns, Packet, RecordData, Records, Soa, Srv, Tlsa};
+
+mod resolv_conf;
+pub use resolv_conf::ResolvConf;
+
+mod search;
+use search::Candidates;
+pub use search::{DEFAULT_NDOTS, MAX_SEARCH};
+
+mod srv;
+pub use srv::{SrvTarget, MAX_SRV};
+
+mod svcb;
+pub use svcb::{
+    AlpnIds, Https, Ipv4Hints, Ipv6Hints, SvcParam, SvcParamKeys, SvcParams, Svcb, MAX_ALIASES,
+};
+
+mod tcp;
+pub use tcp::{NoConnection, NoTcp};
+
+mod txt;
+pub use txt::{Attribute, KeyBytes, Txt, TxtStrings};
+
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
+#[cfg(test)]
+mod mock;
 
 /// DNS client
-pub struct ItsDns<S: UdpStack> {
+pub struct ItsDns<S: UdpStack, C: Clock = NoClock, T: TcpConnect = NoTcp> {
     id: AtomicU16,
     stack: S,
+    clock: C,
+    tcp: T,
+    upstream: Mutex<RefCell<Upstream>>,
+    hosts: Hosts,
+    ndots: u8,
+    timeout_ms: u32,
+    attempts: u8,
+    edns_payload_size: Option<u16>,
+    id_source: IdSource,
+    accept_truncated: bool,
+    strategy: Strategy,
+    transport: Transport,
+    race_buffers: Mutex<RefCell<heapless::Vec<&'static mut Packet, MAX_RACE_BUFFERS>>>,
+    cache: Cache,
+}
+
+/// The servers with their health, and the search domains, which can be replaced
+/// while queries are in flight.
+struct Upstream {
+    servers: heapless::Vec<ServerStats, MAX_SERVERS>,
+    search: heapless::Vec<DomainName, MAX_SEARCH>,
+}
+
+/// Size of the buffer responses are received into.
+const PACKET_LEN: usize = MAX_UDP_PAYLOAD as usize;
+
+/// Buffers taken from [`ItsDns::set_race_buffers`] for the queries of a race,
+/// given back when dropped.
+struct RaceBuffers<'a> {
+    pool: &'a Mutex<RefCell<heapless::Vec<&'static mut Packet, MAX_RACE_BUFFERS>>>,
+    taken: heapless::Vec<&'static mut Packet, { MAX_SERVERS - 1 }>,
+}
+
+impl<'a> RaceBuffers<'a> {
+    /// Take up to `n` buffers from `pool`.
+    fn take(
+        pool: &'a Mutex<RefCell<heapless::Vec<&'static mut Packet, MAX_RACE_BUFFERS>>>,
+        n: usize,
+    ) -> Self {
+        let mut taken = heapless::Vec::new();
+        critical_section::with(|cs| {
+            let mut pool = pool.borrow_ref_mut(cs);
+            while taken.len() < n {
+                match pool.pop() {
+                    // There is room for the buffers of a race
+                    Some(buffer) => taken.push(buffer).ok(),
+                    None => break,
+                };
+            }
+        });
+        Self { pool, taken }
+    }
+}
+
+impl<'a> Drop for RaceBuffers<'a> {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            let mut pool = self.pool.borrow_ref_mut(cs);
+            while let Some(buffer) = self.taken.pop() {
+                // There is room for the buffers taken from the pool
+                pool.push(buffer).ok();
+            }
+        })
+    }
+}
+
+/// A validated response to a query.
+struct Response<'p> {
     server: SocketAddr,
+    message: &'p [u8],
+    m: DnsMessage<'p>,
+    layout: Layout,
 }
 
 impl<S: UdpStack> ItsDns<S> {
     /// Create a new DNS client using the UDP stack and a DNS server.
+    ///
+    /// Without a [`Clock`], queries do not time out and nothing is cached. Query
+    /// ids are predictable, as with the default [`Config`], so this is only
+    /// suitable on trusted networks. Use [`ItsDns::with_config`] for more
+    /// control.
     pub fn new(stack: S, server: SocketAddr) -> Self {
+        let mut servers = heapless::Vec::new();
+        // There is room for at least one server
+        servers.push(ServerStats::new(server)).ok();
+        Self::from_parts(
+            stack,
+            NoClock,
+            servers,
+            heapless::Vec::new(),
+            &Config::new().cache_size(0),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: UdpStack> ItsDns<S, StdClock> {
+    /// Create a new DNS client configured like the system resolver, from
+    /// `/etc/resolv.conf` and `/etc/hosts`.
+    ///
+    /// A missing resolv.conf means the defaults, with a server on the local
+    /// host, and a missing hosts file means no entries. Like the servers and
+    /// search domains beyond the limits of resolv.conf, only the first
+    /// [`MAX_HOSTS`] entries of the hosts file are kept, not counting the
+    /// built-in loopback names and multicast addresses.
+    pub fn from_system(stack: S) -> std::io::Result<Self> {
+        use std::io::{Error, ErrorKind};
+
+        fn read(path: &str) -> std::io::Result<std::string::String> {
+            match std::fs::read_to_string(path) {
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(std::string::String::new()),
+                result => result,
+            }
+        }
+
+        let resolv_conf = read("/etc/resolv.conf")?;
+        let config = ResolvConf::parse(&resolv_conf);
+        let mut client = Self::with_config(stack, StdClock::new(), &config.config())
+            .map_err(|e| Error::new(ErrorKind::InvalidData, std::format!("{}", e)))?;
+        // Entries beyond the first MAX_HOSTS are left out
+        client.hosts.load(&read("/etc/hosts")?).ok();
+        Ok(client)
+    }
+}
+
+impl<S: UdpStack, C: Clock> ItsDns<S, C> {
+    /// Create a new DNS client using the UDP stack, a clock for timeouts and
+    /// cache expiry, and a configuration.
+    pub fn with_config(stack: S, clock: C, config: &Config<'_>) -> Result<Self, ConfigError> {
+        let servers = server_list(config.servers)?;
+        if config.attempts == 0 {
+            return Err(ConfigError::NoAttempts);
+        }
+        if config.cache_size > MAX_CACHE {
+            return Err(ConfigError::CacheTooLarge);
+        }
+        if let Some(size) = config.edns_payload_size {
+            if !(512..=MAX_UDP_PAYLOAD).contains(&size) {
+                return Err(ConfigError::InvalidPayloadSize);
+            }
+        }
+        if let Strategy::Race { fanout, .. } = config.strategy {
+            if fanout == 0 || fanout as usize > MAX_SERVERS {
+                return Err(ConfigError::InvalidFanout);
+            }
+        }
+        let search = search_list(config.search)?;
+        Ok(Self::from_parts(stack, clock, servers, search, config))
+    }
+
+    fn from_parts(
+        stack: S,
+        clock: C,
+        servers: heapless::Vec<ServerStats, MAX_SERVERS>,
+        search: heapless::Vec<DomainName, MAX_SEARCH>,
+        config: &Config<'_>,
+    ) -> Self {
         Self {
             id: AtomicU16::new(0),
             stack,
-            server,
+            clock,
+            tcp: NoTcp,
+            upstream: Mutex::new(RefCell::new(Upstream { servers, search })),
+            hosts: Hosts::new(),
+            ndots: config.ndots,
+            timeout_ms: config.timeout_ms,
+            attempts: config.attempts,
+            edns_payload_size: config.edns_payload_size,
+            id_source: config.id_source,
+            accept_truncated: config.accept_truncated,
+            strategy: config.strategy,
+            transport: config.transport,
+            race_buffers: Mutex::new(RefCell::new(heapless::Vec::new())),
+            cache: Cache::new(config.cache_size),
+        }
+    }
+
+    /// Use a TCP stack to ask again for responses that were truncated, with
+    /// [`Transport::UdpThenTcp`].
+    pub fn with_tcp<T: TcpConnect>(self, tcp: T) -> ItsDns<S, C, T> {
+        ItsDns {
+            id: self.id,
+            stack: self.stack,
+            clock: self.clock,
+            tcp,
+            upstream: self.upstream,
+            hosts: self.hosts,
+            ndots: self.ndots,
+            timeout_ms: self.timeout_ms,
+            attempts: self.attempts,
+            edns_payload_size: self.edns_payload_size,
+            id_source: self.id_source,
+            accept_truncated: self.accept_truncated,
+            strategy: self.strategy,
+            transport: self.transport,
+            race_buffers: self.race_buffers,
+            cache: self.cache,
+        }
+    }
+}
+
+impl<S: UdpStack, C: Clock, T: TcpConnect> ItsDns<S, C, T> {
+    /// Replace the servers and search domains, for instance with ones learnt
+    /// from DHCP or router advertisements, optionally flushing the cache.
+    ///
+    /// This can be called while lookups are in progress. Queries already sent
+    /// finish with the previous servers. The [health](Self::server_stats) of all
+    /// servers starts afresh, including servers that are kept.
+    pub fn reconfigure(
+        &self,
+        servers: &[SocketAddr],
+        search: &[&str],
+        flush_cache: bool,
+    ) -> Result<(), ConfigError> {
+        let upstream = Upstream {
+            servers: server_list(servers)?,
+            search: search_list(search)?,
+        };
+        self.set_upstream(upstream, flush_cache);
+        Ok(())
+    }
+
+    /// Replace the servers and search domains with ones learnt from DHCP or
+    /// router advertisements, optionally flushing the cache.
+    ///
+    /// This can be called while lookups are in progress, like
+    /// [`reconfigure`](Self::reconfigure).
+    pub fn apply(&self, config: &NetworkConfig, flush_cache: bool) -> Result<(), ConfigError> {
+        let upstream = Upstream {
+            servers: server_list(config.servers())?,
+            search: config.search().iter().cloned().collect(),
+        };
+        self.set_upstream(upstream, flush_cache);
+        Ok(())
+    }
+
+    fn set_upstream(&self, upstream: Upstream, flush_cache: bool) {
+        critical_section::with(|cs| self.upstream.replace(cs, upstream));
+        if flush_cache {
+            self.cache.clear();
         }
     }
 
+    /// The servers, in the configured order.
+    pub fn servers(&self) -> heapless::Vec<SocketAddr, MAX_SERVERS> {
+        critical_section::with(|cs| {
+            let upstream = self.upstream.borrow_ref(cs);
+            upstream.servers.iter().map(|s| s.server).collect()
+        })
+    }
+
+    /// The health of the servers, in the configured order.
+    pub fn server_stats(&self) -> heapless::Vec<ServerStats, MAX_SERVERS> {
+        critical_section::with(|cs| self.upstream.borrow_ref(cs).servers.clone())
+    }
+
+    /// The servers in the order to try them, by their health.
+    fn server_order(&self) -> heapless::Vec<SocketAddr, MAX_SERVERS> {
+        let now = self.clock.now_ms();
+        critical_section::with(|cs| health::order(&self.upstream.borrow_ref(cs).servers, now))
+    }
+
+    /// Update the health of `server`, unless it was replaced in the meantime.
+    fn record(&self, server: SocketAddr, f: impl FnOnce(&mut ServerStats)) {
+        critical_section::with(|cs| {
+            let mut upstream = self.upstream.borrow_ref_mut(cs);
+            if let Some(stats) = upstream.servers.iter_mut().find(|s| s.server == server) {
+                f(stats);
+            }
+        })
+    }
+
+    /// Set the domains appended to names that are not absolute, tried in order.
+    pub fn set_search(&mut self, domains: &[&str]) -> Result<(), ConfigError> {
+        self.upstream.get_mut().get_mut().search = search_list(domains)?;
+        Ok(())
+    }
+
+    /// The search domains.
+    pub fn search(&self) -> heapless::Vec<DomainName, MAX_SEARCH> {
+        critical_section::with(|cs| self.upstream.borrow_ref(cs).search.clone())
+    }
+
+    /// Set the number of dots a name needs to be tried as is before the search
+    /// domains. Names with fewer dots are tried as is after them.
+    pub fn set_ndots(&mut self, ndots: u8) {
+        self.ndots = ndots;
+    }
+
+    /// The number of dots a name needs to be tried as is first.
+    pub fn ndots(&self) -> u8 {
+        self.ndots
+    }
+
+    /// The static hosts table, consulted before the network.
+    pub fn hosts(&self) -> &Hosts {
+        &self.hosts
+    }
+
+    /// Mutable access to the static hosts table.
+    pub fn hosts_mut(&mut self) -> &mut Hosts {
+        &mut self.hosts
+    }
+
+    /// Remove all cached addresses.
+    pub fn flush_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// Give the buffers that [`Strategy::Race`] receives responses into, besides
+    /// the one of the lookup, replacing any given before. At most
+    /// [`MAX_RACE_BUFFERS`] are used.
+    ///
+    /// Each query takes up to one buffer less than the fanout while it races,
+    /// so that lookups do not carry buffers when queries are not raced. A query
+    /// that finds fewer buffers has fewer queries in flight, down to one at a
+    /// time without any.
+    pub fn set_race_buffers(&mut self, buffers: &'static mut [Packet]) {
+        let pool = self.race_buffers.get_mut().get_mut();
+        pool.clear();
+        pool.extend(buffers.iter_mut().take(MAX_RACE_BUFFERS));
+    }
+
     /// Lookup a host by the name and return the IP address of the host.
+    ///
+    /// This is the first of the addresses from
+    /// [`get_host_addrs`](Self::get_host_addrs), so with [`AddrType::Either`]
+    /// both A and AAAA records are queried, and an IPv6 address is preferred.
     pub async fn get_host_by_name(
         &self,
         host: &str,
-        _addr_type: AddrType,
+        addr_type: AddrType,
     ) -> Result<IpAddr, Error<S::Error>> {
-        let mut packet = [0; 512];
+        let addrs = self.get_host_addrs(host, addr_type).await?;
+        addrs.first().copied().ok_or(Error::NotFound)
+    }
+
+    /// Lookup all addresses of a host by the name, up to [`MAX_ADDRS`], in the
+    /// order to try connecting to them.
+    ///
+    /// IPv4 and IPv6 literals, such as `192.168.1.10`, `[::1]` or `fe80::1%eth0`,
+    /// are returned as is without sending a query. A literal of a different
+    /// family than `addr_type` gives [`Error::NotFound`]. Names in the
+    /// [hosts table](Self::hosts) are also answered without a query.
+    ///
+    /// Names that do not end with a dot are qualified with the
+    /// [search domains](Self::set_search), stopping at the first name that has
+    /// an address.
+    ///
+    /// With [`AddrType::Either`], the AAAA and A queries are sent at the same
+    /// time as in Happy Eyeballs (RFC 8305). When one family answers first, the
+    /// other one is waited for up to [`RESOLUTION_DELAY_MS`], unless the first
+    /// had no addresses. The addresses are sorted by the default precedence of
+    /// RFC 6724 and the families interleaved, starting with the preferred one.
+    pub async fn get_host_addrs(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<heapless::Vec<IpAddr, MAX_ADDRS>, Error<S::Error>> {
+        let mut addrs = heapless::Vec::new();
+        if let Some(ip) = parse_ip_literal(host) {
+            if !matches_type(&ip, &addr_type) {
+                return Err(Error::NotFound);
+            }
+            // The list has room for at least one address
+            addrs.push(ip).ok();
+            return Ok(addrs);
+        }
+        let hosts = |addr_type| {
+            let ip = self.hosts.lookup(&Domain::String(host), &addr_type);
+            ip.into_iter().collect::<heapless::Vec<_, 1>>()
+        };
+        let addrs = match addr_type {
+            AddrType::Either => eyeballs::interleave(hosts(AddrType::IPv6), hosts(AddrType::IPv4)),
+            _ => hosts(addr_type.clone()).into_iter().collect(),
+        };
+        if !addrs.is_empty() {
+            return Ok(addrs);
+        }
+
+        let name =
+            DomainName::from_str(host).map_err(|e| Error::Dns(DnsError::InvalidDomain(e)))?;
+        let mut result = Err(Error::NotFound);
+        // The domains as they are now, should they be replaced during the lookup
+        let search = self.search();
+        for name in Candidates::new(host, name, &search, self.ndots) {
+            result = self.resolve(&name.as_domain(), &addr_type).await;
+            match result {
+                Err(Error::NxDomain { .. }) | Err(Error::NoData { .. }) => {}
+                result => return result,
+            }
+        }
+        result
+    }
+
+    /// Resolve a host and port, or a service, to socket addresses, up to
+    /// [`MAX_ADDRS`], in the order to try connecting to them. This is like
+    /// `ToSocketAddrs` in std, and `target` is one of:
+    ///
+    /// - A host and port, such as `broker.example.net:8883` or `192.0.2.1:53`,
+    ///   the host being looked up as with [`get_host_addrs`](Self::get_host_addrs).
+    /// - An IPv6 address in brackets and a port, such as `[2001:db8::1]:53`.
+    /// - A service name starting with an underscore, such as
+    ///   `_mqtt._tcp.example.net`, whose targets are looked up as with
+    ///   [`lookup_srv`](Self::lookup_srv), taking the addresses of each target
+    ///   in turn with the port of the target.
+    ///
+    /// Anything else gives [`Error::InvalidAddress`], and a service without any
+    /// addresses [`Error::NotFound`].
+    pub async fn get_socket_addrs(
+        &self,
+        target: &str,
+        addr_type: AddrType,
+    ) -> Result<heapless::Vec<SocketAddr, MAX_ADDRS>, Error<S::Error>> {
+        match parse_target(target).ok_or(Error::InvalidAddress)? {
+            Target::Host(host, port) => {
+                let addrs = self.get_host_addrs(host, addr_type).await?;
+                Ok(addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect())
+            }
+            Target::Service(name) => {
+                let targets = self.lookup_srv(name, addr_type).await?;
+                let addrs: heapless::Vec<_, MAX_ADDRS> = targets
+                    .iter()
+                    .flat_map(|t| t.addrs.iter().map(|ip| SocketAddr::new(*ip, t.port)))
+                    .take(MAX_ADDRS)
+                    .collect();
+                match addrs.is_empty() {
+                    true => Err(Error::NotFound),
+                    false => Ok(addrs),
+                }
+            }
+        }
+    }
+
+    /// Query the addresses of `name` of `addr_type`, both families as in
+    /// [`get_host_addrs`](Self::get_host_addrs) with [`AddrType::Either`].
+    async fn resolve(
+        &self,
+        name: &Domain<'_>,
+        addr_type: &AddrType,
+    ) -> Result<heapless::Vec<IpAddr, MAX_ADDRS>, Error<S::Error>> {
+        match addr_type {
+            AddrType::IPv4 => self.query_addrs(name, QType::A).await,
+            AddrType::IPv6 => self.query_addrs(name, QType::AAAA).await,
+            AddrType::Either => self.query_dual(name).await,
+        }
+    }
+
+    /// Query the AAAA and A records of `name` at the same time, returning the
+    /// addresses in the order of RFC 8305.
+    async fn query_dual(
+        &self,
+        name: &Domain<'_>,
+    ) -> Result<heapless::Vec<IpAddr, MAX_ADDRS>, Error<S::Error>> {
+        let mut aaaa = pin!(self.query_addrs(name, QType::AAAA));
+        let mut a = pin!(self.query_addrs(name, QType::A));
+        let (v6_first, first) = poll_fn(|cx| {
+            if let Poll::Ready(r) = aaaa.as_mut().poll(cx) {
+                return Poll::Ready((true, r));
+            }
+            a.as_mut().poll(cx).map(|r| (false, r))
+        })
+        .await;
+
+        // Wait a little for the other family, or as long as it takes if the
+        // first one has no addresses
+        let other = if v6_first { a.as_mut() } else { aaaa.as_mut() };
+        let other = match first {
+            Ok(_) => timeout(&self.clock, RESOLUTION_DELAY_MS, other).await,
+            Err(_) => Some(other.await),
+        };
+        let (v6, v4) = match v6_first {
+            true => (Some(first), other),
+            false => (other, Some(first)),
+        };
+        match (v6, v4) {
+            (Some(Err(e6)), Some(Err(e4))) => Err(match e4 {
+                Error::NoData { .. } => e6,
+                e4 => e4,
+            }),
+            (v6, v4) => Ok(eyeballs::interleave(
+                v6.and_then(Result::ok).unwrap_or_default(),
+                v4.and_then(Result::ok).unwrap_or_default(),
+            )),
+        }
+    }
+
+    /// Query the addresses of `name`, with `qtype` A or AAAA, or use cached ones.
+    async fn query_addrs(
+        &self,
+        name: &Domain<'_>,
+        qtype: QType,
+    ) -> Result<heapless::Vec<IpAddr, MAX_ADDRS>, Error<S::Error>> {
+        let addr_type = match qtype {
+            QType::AAAA => AddrType::IPv6,
+            _ => AddrType::IPv4,
+        };
+        let addrs = self.cache.get(name, &addr_type, self.clock.now_ms());
+        if !addrs.is_empty() {
+            return Ok(addrs);
+        }
+
+        let mut packet = [0; PACKET_LEN];
+        let r = self.query(*name, qtype, &mut packet).await?;
+        let now = self.clock.now_ms();
+        let mut addrs = heapless::Vec::new();
+        for answer in r.m.answers.iter() {
+            let answer = answer.map_err(Error::Dns)?;
+            if answer.domain != *name || answer.r#type != qtype {
+                continue;
+            }
+            let ip = match address(&answer) {
+                Some(ip) => ip,
+                None => continue,
+            };
+            self.cache.insert(name, ip, answer.ttl, now);
+            addrs.push(ip).ok();
+        }
+        match addrs.is_empty() {
+            true => Err(Error::NoData { server: r.server }),
+            false => Ok(addrs),
+        }
+    }
+
+    /// Lookup the targets of a service, such as `_mqtt._tcp.example.net`, by its
+    /// SRV records (RFC 2782). Only the [`MAX_SRV`] targets of the lowest
+    /// priorities are kept.
+    ///
+    /// The targets are ordered by priority, and those of the same priority by a
+    /// random selection weighted by their weight, using the [`IdSource`] for
+    /// randomness. The addresses of each target, of `addr_type`, are taken from
+    /// the additional section when the server included them, and looked up
+    /// otherwise. A target whose addresses can not be found has none.
+    ///
+    /// The name is used as is, without the search domains. A service that is
+    /// decidedly not available, with a single target `.`, has no targets.
+    pub async fn lookup_srv(
+        &self,
+        name: &str,
+        addr_type: AddrType,
+    ) -> Result<heapless::Vec<SrvTarget, MAX_SRV>, Error<S::Error>> {
+        let invalid = |e| Error::Dns(DnsError::InvalidDomain(e));
+        let name = DomainName::from_str(name).map_err(invalid)?;
+        let name = name.as_domain();
+        let mut targets = {
+            let mut packet = [0; PACKET_LEN];
+            let r = self.query(name, Srv::TYPE, &mut packet).await?;
+            let mut targets = heapless::Vec::<SrvTarget, MAX_SRV>::new();
+            for answer in r.m.answers.iter() {
+                let answer = answer.map_err(Error::Dns)?;
+                if answer.domain != name || answer.r#type != Srv::TYPE {
+                    continue;
+                }
+                let srv = Srv::decode(&answer, r.message).map_err(Error::Dns)?;
+                let target = SrvTarget {
+                    priority: srv.priority,
+                    weight: srv.weight,
+                    port: srv.port,
+                    target: DomainName::from_domain(&srv.target).map_err(invalid)?,
+                    addrs: heapless::Vec::new(),
+                };
+                insert_by_key(&mut targets, target, |t| t.priority);
+            }
+            match &targets[..] {
+                [] => return Err(Error::NoData { server: r.server }),
+                [t] if t.target.is_root() => return Ok(heapless::Vec::new()),
+                _ => {}
+            }
+
+            for t in targets.iter_mut() {
+                t.addrs =
+                    glue(r.m.additionals.iter(), &t.target, &addr_type).map_err(Error::Dns)?;
+            }
+            targets
+        };
+
+        srv::order(&mut targets, || self.random());
+        let hosts = targets.iter_mut().map(|t| (&t.target, &mut t.addrs));
+        fill_addrs(self, hosts, &addr_type).await;
+        Ok(targets)
+    }
+
+    /// Lookup the mail exchanges of a domain by its MX records, sorted by
+    /// preference. Only the [`MAX_MX`] most preferred are kept.
+    ///
+    /// The addresses of each exchange, of `addr_type`, are taken from the
+    /// additional section when the server included them, and looked up
+    /// otherwise. An exchange whose addresses can not be found has none.
+    ///
+    /// A domain without MX records is its own exchange, with a preference of 0,
+    /// if it has addresses (RFC 5321). A domain that accepts no mail, with a
+    /// single exchange `.` (RFC 7505), has no exchanges. The name is used as is,
+    /// without the search domains.
+    pub async fn lookup_mx(
+        &self,
+        domain: &str,
+        addr_type: AddrType,
+    ) -> Result<heapless::Vec<MailExchange, MAX_MX>, Error<S::Error>> {
+        let invalid = |e| Error::Dns(DnsError::InvalidDomain(e));
+        let domain = DomainName::from_str(domain).map_err(invalid)?;
+        let mut exchanges = {
+            let mut packet = [0; PACKET_LEN];
+            let r = self
+                .query(domain.as_domain(), Mx::TYPE, &mut packet)
+                .await?;
+            let mut exchanges = heapless::Vec::<MailExchange, MAX_MX>::new();
+            for answer in r.m.answers.iter() {
+                let answer = answer.map_err(Error::Dns)?;
+                if domain != answer.domain || answer.r#type != Mx::TYPE {
+                    continue;
+                }
+                let mx = Mx::decode(&answer, r.message).map_err(Error::Dns)?;
+                let exchange = DomainName::from_domain(&mx.exchange).map_err(invalid)?;
+                let addrs =
+                    glue(r.m.additionals.iter(), &exchange, &addr_type).map_err(Error::Dns)?;
+                let exchange = MailExchange {
+                    preference: mx.preference,
+                    exchange,
+                    addrs,
+                };
+                insert_by_key(&mut exchanges, exchange, |mx| mx.preference);
+            }
+            exchanges
+        };
+
+        match &exchanges[..] {
+            [] => {
+                let addrs = self.resolve(&domain.as_domain(), &addr_type).await?;
+                let implicit = MailExchange {
+                    preference: 0,
+                    exchange: domain,
+                    addrs,
+                };
+                // There is room for one exchange
+                exchanges.push(implicit).ok();
+                return Ok(exchanges);
+            }
+            [mx] if mx.exchange.is_root() => return Ok(heapless::Vec::new()),
+            _ => {}
+        }
+
+        let hosts = exchanges.iter_mut().map(|mx| (&mx.exchange, &mut mx.addrs));
+        fill_addrs(self, hosts, &addr_type).await;
+        Ok(exchanges)
+    }
+
+    /// Lookup the authoritative name servers of a zone by its NS records, up to
+    /// [`MAX_NS`], with their addresses of `addr_type` from the additional
+    /// section. The name is used as is, without the search domains.
+    pub async fn lookup_ns(
+        &self,
+        zone: &str,
+        addr_type: AddrType,
+    ) -> Result<heapless::Vec<NameServer, MAX_NS>, Error<S::Error>> {
+        let invalid = |e| Error::Dns(DnsError::InvalidDomain(e));
+        let mut packet = Packet::new();
+        let records = self.lookup::<Ns>(zone, &mut packet).await?;
+        let additionals = records.additionals();
+        let mut servers = heapless::Vec::new();
+        for ns in records {
+            let name =
+                DomainName::from_domain(&ns.map_err(Error::Dns)?.nsdname).map_err(invalid)?;
+            let addrs = glue(additionals.clone(), &name, &addr_type).map_err(Error::Dns)?;
+            // Servers beyond the maximum are left out
+            servers.push(NameServer { name, addrs }).ok();
+        }
+        Ok(servers)
+    }
+
+    /// Lookup the SOA record of a zone, as with [`lookup`](Self::lookup).
+    pub async fn lookup_soa<'p>(
+        &self,
+        zone: &str,
+        packet: &'p mut Packet,
+    ) -> Result<Soa<'p>, Error<S::Error>> {
+        let mut records = self.lookup::<Soa>(zone, packet).await?;
+        records.next().ok_or(Error::NotFound)?.map_err(Error::Dns)
+    }
+
+    /// Lookup the HTTPS records of `name`, as with [`lookup`](Self::lookup),
+    /// following aliases (AliasMode records) up to [`MAX_ALIASES`] times.
+    ///
+    /// The records returned are service bindings (ServiceMode records) for
+    /// [`Records::name`], the end of the aliases, in the order of the response.
+    /// An alias to `.`, meaning that the service is not available, gives
+    /// [`Error::NoData`], and too many aliases [`Error::NotFound`].
+    pub async fn lookup_https<'p>(
+        &self,
+        name: &str,
+        packet: &'p mut Packet,
+    ) -> Result<Records<'p, Https<'p>>, Error<S::Error>> {
+        let invalid = |e| Error::Dns(DnsError::InvalidDomain(e));
+        let mut name = DomainName::from_str(name).map_err(invalid)?;
+        for _ in 0..=MAX_ALIASES {
+            let (server, layout, alias) = {
+                let r = self
+                    .query(name.as_domain(), Https::TYPE, &mut packet.0)
+                    .await?;
+                let records = Records::<Https>::new(name.clone(), r.server, r.message, &r.m);
+                let mut found = false;
+                let mut alias = None;
+                for https in records {
+                    let https = https.map_err(Error::Dns)?;
+                    found = true;
+                    if https.is_alias() {
+                        alias = Some(DomainName::from_domain(&https.target).map_err(invalid)?);
+                        break;
+                    }
+                }
+                if !found {
+                    return Err(Error::NoData { server: r.server });
+                }
+                (r.server, r.layout, alias)
+            };
+            match alias {
+                Some(target) if target.is_root() => return Err(Error::NoData { server }),
+                Some(target) => name = target,
+                None => {
+                    // Taken from the layout, as the response outlives the loop
+                    let message = &packet.0[..layout.len];
+                    let m = layout.message(message);
+                    return Ok(Records::new(name, server, message, &m));
+                }
+            }
+        }
+        Err(Error::NotFound)
+    }
+
+    /// Lookup the records of `name` with data of type `R`, such as [`Srv`], which
+    /// are decoded from `packet` without copying as they are iterated.
+    ///
+    /// The name is used as is, without the search domains. A name without
+    /// records of the type gives [`Error::NoData`].
+    pub async fn lookup<'p, R: RecordData<'p>>(
+        &self,
+        name: &str,
+        packet: &'p mut Packet,
+    ) -> Result<Records<'p, R>, Error<S::Error>> {
+        let name =
+            DomainName::from_str(name).map_err(|e| Error::Dns(DnsError::InvalidDomain(e)))?;
+        self.lookup_name(name, packet).await
+    }
+
+    /// Lookup the records of `name` with data of type `R`.
+    async fn lookup_name<'p, R: RecordData<'p>>(
+        &self,
+        name: DomainName,
+        packet: &'p mut Packet,
+    ) -> Result<Records<'p, R>, Error<S::Error>> {
+        let r = self.query(name.as_domain(), R::TYPE, &mut packet.0).await?;
+        let records = || Records::new(name.clone(), r.server, r.message, &r.m);
+        match records().next() {
+            None => Err(Error::NoData { server: r.server }),
+            Some(_) => Ok(records()),
+        }
+    }
+
+    /// Lookup the TLSA records for a service on `port` over `protocol`, such as
+    /// `tcp`, at `host`, as with [`lookup`](Self::lookup) of the
+    /// [owner name](Tlsa::owner_name) such as `_443._tcp.example.com`.
+    ///
+    /// The records should only be used to authenticate the service if
+    /// [`Records::authentic_data`] says that the server validated them.
+    pub async fn lookup_tlsa<'p>(
+        &self,
+        port: u16,
+        protocol: &str,
+        host: &str,
+        packet: &'p mut Packet,
+    ) -> Result<Records<'p, Tlsa<'p>>, Error<S::Error>> {
+        let name = Tlsa::owner_name(port, protocol, host)
+            .map_err(|e| Error::Dns(DnsError::InvalidDomain(e)))?;
+        self.lookup_name(name, packet).await
+    }
+
+    /// Lookup the TXT records of `name`, as with [`lookup`](Self::lookup).
+    ///
+    /// Values longer than 255 octets are split into several strings, which
+    /// [`Txt::value`] joins again, and [`Txt::attributes`] parses the strings of
+    /// a record as `key=value` attributes.
+    pub async fn lookup_txt<'p>(
+        &self,
+        name: &str,
+        packet: &'p mut Packet,
+    ) -> Result<Records<'p, Txt<'p>>, Error<S::Error>> {
+        self.lookup(name, packet).await
+    }
+
+    /// Lookup the name of a host by its IP address, using the hosts table or a
+    /// PTR query.
+    pub async fn get_host_by_address(&self, addr: IpAddr) -> Result<String<256>, Error<S::Error>> {
+        let mut name = String::new();
+        if let Some(domain) = self.hosts.reverse(&addr) {
+            write!(name, "{}", domain).map_err(|_| Error::Dns(DnsError::Encode))?;
+            return Ok(name);
+        }
+
+        let qname = reverse_name(&addr);
+        let mut packet = [0; PACKET_LEN];
+        let r = self
+            .query(Domain::String(&qname), QType::PTR, &mut packet)
+            .await?;
+        for answer in r.m.answers.iter() {
+            let answer = answer.map_err(Error::Dns)?;
+            if answer.domain == Domain::String(&qname) && answer.r#type == QType::PTR {
+                let (_, domain) = Domain::decode(answer.rdata, r.message).map_err(Error::Dns)?;
+                write!(name, "{}", domain).map_err(|_| Error::Dns(DnsError::Encode))?;
+                return Ok(name);
+            }
+        }
+        Err(Error::NoData { server: r.server })
+    }
+
+    /// Send a query for `qname` and receive the response into `packet`, going
+    /// through the servers, healthiest first, until one gives an answer.
+    async fn query<'p>(
+        &self,
+        qname: Domain<'_>,
+        qtype: QType,
+        packet: &'p mut [u8],
+    ) -> Result<Response<'p>, Error<S::Error>> {
+        let servers = self.server_order();
+        let mut result = Err(Error::NotFound);
+        for _ in 0..self.attempts {
+            result = match self.strategy {
+                Strategy::Sequential => {
+                    let next = Cell::new(0);
+                    self.worker(0, &servers, &next, qname, qtype, packet)
+                        .await
+                        .unwrap_or(Err(Error::NotFound))
+                }
+                Strategy::Race { fanout, stagger_ms } => {
+                    self.race(fanout, stagger_ms, &servers, qname, qtype, packet)
+                        .await
+                }
+            };
+            if !fails_over(&result) {
+                break;
+            }
+        }
+
+        let (server, layout) = result?;
+        let message = &packet[..layout.len];
+        Ok(Response {
+            server,
+            message,
+            m: layout.message(message),
+            layout,
+        })
+    }
+
+    /// Race up to `fanout` workers through `servers`, started `stagger_ms` apart,
+    /// returning the first response that does not fail over, received into `packet`.
+    async fn race(
+        &self,
+        fanout: u8,
+        stagger_ms: u32,
+        servers: &[SocketAddr],
+        qname: Domain<'_>,
+        qtype: QType,
+        packet: &mut [u8],
+    ) -> Result<(SocketAddr, Layout), Error<S::Error>> {
+        let workers = (fanout as usize).min(servers.len());
+        let mut spare = RaceBuffers::take(&self.race_buffers, workers.saturating_sub(1));
+        let next = Cell::new(0);
+        let (w, result) = {
+            // A worker for the packet and each spare buffer, and none for the rest
+            let mut buffers = spare.taken.iter_mut().map(|b| &mut b.0[..]);
+            let mut packet = Some(&mut packet[..]);
+            let mut start = |w: usize| {
+                let packet = packet.take().or_else(|| buffers.next())?;
+                let stagger_ms = w as u32 * stagger_ms;
+                Some(self.worker(stagger_ms, servers, &next, qname, qtype, packet))
+            };
+            // Each worker is pinned on its own, one for each of the MAX_SERVERS
+            // servers, which the length of the array checks
+            let mut workers: [_; MAX_SERVERS] = [
+                pin!(start(0)),
+                pin!(start(1)),
+                pin!(start(2)),
+                pin!(start(3)),
+            ];
+            let mut last = None;
+            poll_fn(|cx| {
+                let mut pending = false;
+                for (w, worker) in workers.iter_mut().enumerate() {
+                    let result = match worker.as_mut().as_pin_mut().map(|f| f.poll(cx)) {
+                        Some(Poll::Ready(result)) => result,
+                        Some(Poll::Pending) => {
+                            pending = true;
+                            continue;
+                        }
+                        None => continue,
+                    };
+                    worker.set(None);
+                    match result {
+                        // Dropping the other workers cancels their queries
+                        Some(result) if !fails_over(&result) => return Poll::Ready((w, result)),
+                        Some(result) => last = Some((w, result)),
+                        None => {}
+                    }
+                }
+                match pending {
+                    true => Poll::Pending,
+                    false => Poll::Ready(last.take().unwrap_or((0, Err(Error::NotFound)))),
+                }
+            })
+            .await
+        };
+        if let (Ok((_, layout)), Some(w)) = (&result, w.checked_sub(1)) {
+            packet[..layout.len].copy_from_slice(&spare.taken[w].0[..layout.len]);
+        }
+        result
+    }
+
+    /// Go through `servers` after waiting `stagger_ms`, sharing them with other
+    /// workers through `next`, until a response does not fail over. Returns the
+    /// last result, or `None` if there were no servers left to try.
+    async fn worker(
+        &self,
+        stagger_ms: u32,
+        servers: &[SocketAddr],
+        next: &Cell<usize>,
+        qname: Domain<'_>,
+        qtype: QType,
+        packet: &mut [u8],
+    ) -> Option<Result<(SocketAddr, Layout), Error<S::Error>>> {
+        if stagger_ms > 0 && next.get() < servers.len() {
+            self.clock.delay_ms(stagger_ms).await;
+        }
+        let mut result = None;
+        while let Some(server) = servers.get(next.get()) {
+            next.set(next.get() + 1);
+            let r = self
+                .try_server(*server, qname, qtype, packet)
+                .await
+                .map(|layout| (*server, layout));
+            if !fails_over(&r) {
+                return Some(r);
+            }
+            result = Some(r);
+        }
+        result
+    }
+
+    /// Exchange a query with `server`, recording the outcome in its health.
+    async fn try_server(
+        &self,
+        server: SocketAddr,
+        qname: Domain<'_>,
+        qtype: QType,
+        packet: &mut [u8],
+    ) -> Result<Layout, Error<S::Error>> {
+        let start = self.clock.now_ms();
+        let result = self.exchange(server, qname, qtype, packet).await;
+        let now = self.clock.now_ms();
+        match result {
+            Err(Error::Timeout { .. }) => {
+                self.record(server, |s| s.failure(now, Some(self.timeout_ms)))
+            }
+            Err(Error::Network(_))
+            | Err(Error::Server { .. })
+            | Err(Error::IdMismatch { .. })
+            | Err(Error::Malformed { .. }) => self.record(server, |s| s.failure(now, None)),
+            // Nothing was sent, which says nothing about the server
+            Err(Error::Dns(_)) => {}
+            _ => {
+                let rtt = now.saturating_sub(start).min(u32::MAX as u64) as u32;
+                self.record(server, |s| s.success(rtt));
+            }
+        }
+        result
+    }
+
+    /// A random number from the [`IdSource`], which is the next id if sequential.
+    fn random(&self) -> u16 {
+        match self.id_source {
+            IdSource::Sequential => self.id.fetch_add(1, Ordering::Relaxed),
+            IdSource::Random(random) => random(),
+        }
+    }
+
+    /// Send a query for `qname` to `server` and receive the response into `packet`,
+    /// returning it decoded after checking that it answers the query and is not an
+    /// error. A truncated response is asked for again over TCP if configured.
+    async fn exchange(
+        &self,
+        server: SocketAddr,
+        qname: Domain<'_>,
+        qtype: QType,
+        packet: &mut [u8],
+    ) -> Result<Layout, Error<S::Error>> {
+        let id = self.random();
+        let len = self.encode_query(id, qname, qtype, packet)?;
+        let len = {
+            let (_, mut conn) = self.stack.connect(server).await.map_err(Error::Network)?;
+            conn.send(&packet[..len]).await.map_err(Error::Network)?;
+            timeout(&self.clock, self.timeout_ms, conn.receive_into(packet))
+                .await
+                .ok_or(Error::Timeout { server })?
+                .map_err(Error::Network)?
+        };
+        let accept_truncated = self.accept_truncated && self.transport == Transport::Udp;
+        match self.check(server, id, qname, qtype, &packet[..len], accept_truncated) {
+            Err(Error::Truncated { .. }) if self.transport == Transport::UdpThenTcp => {}
+            result => return result,
+        }
+
+        // The response did not fit in a datagram, so it is asked for again
+        let len = self.encode_query(id, qname, qtype, packet)?;
+        let exchange = tcp::exchange(&self.tcp, server, packet, len);
+        let len = timeout(&self.clock, self.timeout_ms, exchange)
+            .await
+            .flatten()
+            .ok_or(Error::Truncated { server })?;
+        self.check(
+            server,
+            id,
+            qname,
+            qtype,
+            &packet[..len],
+            self.accept_truncated,
+        )
+    }
 
-        let id = self.id.fetch_add(1, Ordering::Relaxed);
-        let len = DnsMessage {
+    /// Encode a query for `qname` with `id` into `packet`, returning its length.
+    fn encode_query(
+        &self,
+        id: u16,
+        qname: Domain<'_>,
+        qtype: QType,
+        packet: &mut [u8],
+    ) -> Result<usize, Error<S::Error>> {
+        DnsMessage {
             id,
             opcode: Opcode::Query,
+            // The AD bit asks the server whether it validated the answer
+            // (RFC 6840)
+            flags: Flags {
+                recursion_desired: true,
+                authentic_data: true,
+                ..Default::default()
+            },
+            rcode: Rcode::NOERROR,
             questions: Questions::Slice(&[Question {
-                qname: Domain::String(host),
-                qtype: QType::A,
+                qname,
+                qtype,
                 qclass: QClass::IN,
             }]),
             answers: Answers::Slice(&[]),
+            authorities: Answers::Slice(&[]),
+            additionals: Answers::Slice(&[]),
+            edns: self.edns_payload_size.map(|udp_payload_size| Edns {
+                udp_payload_size,
+                version: 0,
+                dnssec_ok: false,
+            }),
+        }
+        .encode(packet)
+        .map_err(Error::Dns)
+    }
+
+    /// Decode the response `message` from `server`, checking that it answers
+    /// the query with `id` for `qname` and is not an error.
+    fn check(
+        &self,
+        server: SocketAddr,
+        id: u16,
+        qname: Domain<'_>,
+        qtype: QType,
+        message: &[u8],
+        accept_truncated: bool,
+    ) -> Result<Layout, Error<S::Error>> {
+        let m = Layout::decode(message).map_err(|e| match e {
+            // A truncated response may be cut in the middle of a record
+            DnsError::Decode(_) if message.len() > 2 && message[2] & 0x02 != 0 => {
+                Error::Truncated { server }
+            }
+            DnsError::Decode(error) => Error::Malformed { server, error },
+            e => Error::Dns(e),
+        })?;
+
+        // A forged response has to guess the question as well as the id
+        let question = m.message(message).questions.iter().next();
+        let asked =
+            |q: &Question<'_>| q.qname == qname && q.qtype == qtype && q.qclass == QClass::IN;
+        if m.id != id || !matches!(question, Some(Ok(q)) if asked(&q)) {
+            return Err(Error::IdMismatch {
+                server,
+                expected: id,
+                received: m.id,
+            });
+        }
+        if m.flags.truncated && !accept_truncated {
+            return Err(Error::Truncated { server });
+        }
+        match m.rcode {
+            Rcode::NOERROR => {}
+            Rcode::NXDOMAIN => return Err(Error::NxDomain { server }),
+            rcode => return Err(Error::Server { server, rcode }),
+        }
+        Ok(m)
+    }
+}
+
+/// The address in an A or AAAA record.
+fn address(answer: &Answer<'_>) -> Option<IpAddr> {
+    match (answer.r#type, answer.rdata) {
+        (QType::A, &[a, b, c, d]) => Some(IpAddr::V4(Ipv4Addr::new(a, b, c, d))),
+        (QType::AAAA, rdata) if rdata.len() == 16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(rdata);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// The addresses of `name` of `addr_type` in a section, such as the glue in the
+/// additional section, ordered as in RFC 8305.
+fn glue(
+    section: AnswerIter<'_>,
+    name: &DomainName,
+    addr_type: &AddrType,
+) -> Result<heapless::Vec<IpAddr, MAX_ADDRS>, DnsError> {
+    let mut v6 = heapless::Vec::<_, MAX_ADDRS>::new();
+    let mut v4 = heapless::Vec::<_, MAX_ADDRS>::new();
+    for answer in section {
+        let answer = answer?;
+        if *name != answer.domain {
+            continue;
+        }
+        match address(&answer) {
+            Some(ip @ IpAddr::V6(_)) => v6.push(ip).ok(),
+            Some(ip @ IpAddr::V4(_)) => v4.push(ip).ok(),
+            None => None,
+        };
+    }
+    Ok(match addr_type {
+        AddrType::IPv4 => v4,
+        AddrType::IPv6 => v6,
+        AddrType::Either => eyeballs::interleave(v6, v4),
+    })
+}
+
+/// Look up the addresses of the hosts that got none from [`glue`], leaving them
+/// empty if the lookup fails.
+async fn fill_addrs<'h, S: UdpStack, C: Clock, T: TcpConnect>(
+    dns: &ItsDns<S, C, T>,
+    hosts: impl Iterator<Item = (&'h DomainName, &'h mut heapless::Vec<IpAddr, MAX_ADDRS>)>,
+    addr_type: &AddrType,
+) {
+    for (host, addrs) in hosts {
+        if addrs.is_empty() && !host.is_root() {
+            if let Ok(found) = dns.resolve(&host.as_domain(), addr_type).await {
+                *addrs = found;
+            }
         }
-        .encode(&mut packet[..])
-        .map_err(Error::Dns)?;
+    }
+}
 
-        match self.stack.connect(self.server).await {
-            Ok((_, mut server)) => {
-                server.send(&packet[..len]).await.map_err(Error::Network)?;
+/// Sort by a key, keeping the order of the answers for equal keys.
+pub(crate) fn sort_stable_by_key<T, K: Ord>(items: &mut [T], key: impl Fn(&T) -> K) {
+    for i in 1..items.len() {
+        let mut j = i;
+        while j > 0 && key(&items[j - 1]) > key(&items[j]) {
+            items.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
 
-                let len = server
-                    .receive_into(&mut packet[..])
-                    .await
-                    .map_err(Error::Network)?;
+/// Insert a record into results sorted by a key, after those with an equal key.
+/// When the results are full, the record with the largest key is left out, so
+/// that the best records are kept whatever their order in the answers.
+fn insert_by_key<T, K: Ord, const N: usize>(
+    results: &mut heapless::Vec<T, N>,
+    record: T,
+    key: impl Fn(&T) -> K,
+) {
+    let k = key(&record);
+    let at = results
+        .iter()
+        .position(|r| key(r) > k)
+        .unwrap_or(results.len());
+    if results.is_full() {
+        if at == results.len() {
+            return;
+        }
+        results.pop();
+    }
+    // There is room now
+    results.push(record).ok();
+    results[at..].rotate_right(1);
+}
 
-                let m = DnsMessage::decode(&packet[..len]).map_err(Error::Dns)?;
+/// Returns true if another server could answer differently.
+fn fails_over<T, N>(result: &Result<T, Error<N>>) -> bool {
+    matches!(
+        result,
+        Err(Error::Network(_))
+            | Err(Error::Timeout { .. })
+            | Err(Error::Server { .. })
+            | Err(Error::IdMismatch { .. })
+            | Err(Error::Malformed { .. })
+    )
+}
 
-                for answer in 0..m.answers.count() {
-                    if let Some(answer) = m.answers.get(answer).map_err(Error::Dns)? {
-                        if answer.domain == Domain::String(host)
-                            && answer.r#type == QType::A
-                            && answer.rdata.len() >= 4
-                        {
-                            let ip = answer.rdata;
-                            return Ok(IpAddr::V4(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])));
-                        }
-                    }
-                }
-                Err(Error::NotFound)
+/// Check a list of servers, starting their health afresh.
+fn server_list(
+    servers: &[SocketAddr],
+) -> Result<heapless::Vec<ServerStats, MAX_SERVERS>, ConfigError> {
+    if servers.is_empty() {
+        return Err(ConfigError::NoServers);
+    }
+    if servers.len() > MAX_SERVERS {
+        return Err(ConfigError::TooManyServers);
+    }
+    Ok(servers
+        .iter()
+        .map(|&server| ServerStats::new(server))
+        .collect())
+}
+
+/// Parse a list of search domains.
+fn search_list(domains: &[&str]) -> Result<heapless::Vec<DomainName, MAX_SEARCH>, ConfigError> {
+    let mut search = heapless::Vec::new();
+    for domain in domains {
+        let name = DomainName::from_str(domain).map_err(ConfigError::InvalidSearchDomain)?;
+        search
+            .push(name)
+            .map_err(|_| ConfigError::TooManySearchDomains)?;
+    }
+    Ok(search)
+}
+
+/// The name used for reverse lookups of `addr`, below `in-addr.arpa` or `ip6.arpa`.
+fn reverse_name(addr: &IpAddr) -> String<72> {
+    let mut name = String::new();
+    // The names always fit
+    match addr {
+        IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            write!(name, "{}.{}.{}.{}.in-addr.arpa", d, c, b, a).ok();
+        }
+        IpAddr::V6(ip) => {
+            for b in ip.octets().iter().rev() {
+                write!(name, "{:x}.{:x}.", b & 0xF, b >> 4).ok();
             }
-            Err(e) => Err(Error::Network(e)),
+            name.push_str("ip6.arpa").ok();
         }
     }
+    name
+}
+
+/// What [`ItsDns::get_socket_addrs`] resolves.
+#[derive(Debug, PartialEq, Eq)]
+enum Target<'a> {
+    /// A host, which may be an address literal, and a port.
+    Host(&'a str, u16),
+    /// A service name to lookup SRV records of.
+    Service(&'a str),
+}
+
+/// Split a host and port, or recognize a service name.
+fn parse_target(target: &str) -> Option<Target<'_>> {
+    let (host, port) = match target.rsplit_once(':') {
+        Some(split) => split,
+        None if target.starts_with('_') => return Some(Target::Service(target)),
+        None => return None,
+    };
+    let port = match port.as_bytes() {
+        [b'0'..=b'9', ..] => port.parse().ok()?,
+        _ => return None,
+    };
+    // IPv6 addresses need brackets to be told apart from the port
+    let valid = match host.strip_prefix('[') {
+        Some(_) => matches!(parse_ip_literal(host), Some(IpAddr::V6(_))),
+        None => !host.is_empty() && !host.contains(':'),
+    };
+    valid.then_some(Target::Host(host, port))
+}
+
+/// Parse an IPv4 or IPv6 address literal. IPv6 addresses may be enclosed in
+/// brackets, which only they may be (RFC 3986), and carry a zone index, which
+/// is ignored.
+fn parse_ip_literal(host: &str) -> Option<IpAddr> {
+    let (host, bracketed) = match host.strip_prefix('[') {
+        Some(inner) => (inner.strip_suffix(']')?, true),
+        None => (host, false),
+    };
+    if let Ok(ip) = Ipv4Addr::from_str(host) {
+        return (!bracketed).then_some(IpAddr::V4(ip));
+    }
+    let addr = match host.split_once('%') {
+        Some((addr, zone)) if !zone.is_empty() => addr,
+        Some(_) => return None,
+        None => host,
+    };
+    Ipv6Addr::from_str(addr).ok().map(IpAddr::V6)
 }
 
-impl<S: UdpStack> Dns for ItsDns<S> {
+impl<S: UdpStack, C: Clock, T: TcpConnect> Dns for ItsDns<S, C, T> {
     type Error = Error<S::Error>;
 
     async fn get_host_by_name(
@@ -122,7 +1420,780 @@ impl<S: UdpStack> Dns for ItsDns<S> {
         ItsDns::get_host_by_name(self, host, addr_type).await
     }
 
-    async fn get_host_by_address(&self, _addr: IpAddr) -> Result<String<256>, Self::Error> {
-        todo!()
+    async fn get_host_by_address(&self, addr: IpAddr) -> Result<String<256>, Self::Error> {
+        ItsDns::get_host_by_address(self, addr).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{a, server, MockStack, Reply, Server};
+    use std::boxed::Box;
+    use std::string::ToString;
+    use std::vec;
+
+    fn client<'a>(
+        stack: &'a MockStack,
+        config: Config<'_>,
+    ) -> ItsDns<&'a MockStack, mock::MockClock> {
+        let servers: std::vec::Vec<SocketAddr> = stack.servers.iter().map(|s| s.addr).collect();
+        ItsDns::with_config(stack, stack.clock.clone(), &config.servers(&servers)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let stack = MockStack::new(vec![
+            Server::new(1, |_| Reply::Silent),
+            Server::new(2, |_| Reply::Rcode(Rcode::SERVFAIL)),
+            Server::new(3, a([192, 0, 2, 10])),
+        ]);
+        let dns = client(&stack, Config::new().timeout_ms(100));
+        let ip = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)), ip.unwrap());
+        assert_eq!([1, 1, 1], [0, 1, 2].map(|i| stack.queries(i)));
+
+        // Cached
+        let ip = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)), ip.unwrap());
+        assert_eq!(1, stack.queries(2));
+        // The failing servers are avoided
+        dns.flush_cache();
+        dns.get_host_by_name("example.com", AddrType::IPv4)
+            .await
+            .unwrap();
+        assert_eq!([1, 1, 2], [0, 1, 2].map(|i| stack.queries(i)));
+    }
+
+    #[tokio::test]
+    async fn test_forged_question() {
+        let forged = |_: &Question<'_>| {
+            let reply = Reply::Records(vec![(QType::A, vec![192, 0, 2, 66])]);
+            Reply::Forged("example.com", QType::AAAA, Box::new(reply))
+        };
+        let stack = MockStack::new(vec![
+            Server::new(1, forged),
+            Server::new(2, a([192, 0, 2, 10])),
+        ]);
+        let dns = client(&stack, Config::new());
+        let ip = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)), ip.unwrap());
+        assert_eq!([1, 1], [0, 1].map(|i| stack.queries(i)));
+
+        let stack = MockStack::new(vec![Server::new(1, |_| {
+            let reply = Reply::Records(vec![(QType::A, vec![192, 0, 2, 66])]);
+            Reply::Forged("example.net", QType::A, Box::new(reply))
+        })]);
+        let dns = client(&stack, Config::new());
+        let result = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert!(matches!(result, Err(Error::IdMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_fallback() {
+        let stack = MockStack::new(vec![Server::new(1, |q| match q.qtype {
+            QType::A => {
+                let reply = Reply::Records(vec![(QType::A, vec![192, 0, 2, 10])]);
+                Reply::Truncated(Box::new(reply))
+            }
+            _ => Reply::Records(vec![]),
+        })]);
+        let dns = client(&stack, Config::new());
+        let result = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert!(matches!(result, Err(Error::Truncated { .. })));
+
+        // Without a TCP stack
+        let config = Config::new().transport(Transport::UdpThenTcp);
+        let dns = client(&stack, config);
+        let result = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert!(matches!(result, Err(Error::Truncated { .. })));
+        assert_eq!(0, stack.servers[0].tcp_queries.get());
+
+        let dns = client(&stack, config).with_tcp(&stack);
+        let ip = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)), ip.unwrap());
+        assert_eq!(1, stack.servers[0].tcp_queries.get());
+    }
+
+    #[tokio::test]
+    async fn test_server_health() {
+        let stack = MockStack::new(vec![
+            Server::new(1, |_| Reply::Rcode(Rcode::SERVFAIL)),
+            Server::new(2, a([192, 0, 2, 10])).delay(30),
+            Server::new(3, a([192, 0, 2, 10])).delay(10),
+        ]);
+        let dns = client(&stack, Config::new().cache_size(0));
+        let queries = || [0, 1, 2].map(|i| stack.queries(i));
+        dns.get_host_by_name("a.example.com", AddrType::IPv4)
+            .await
+            .unwrap();
+        assert_eq!([1, 1, 0], queries());
+        // Servers not used yet are tried before known ones, then the fastest
+        for _ in 0..2 {
+            dns.get_host_by_name("a.example.com", AddrType::IPv4)
+                .await
+                .unwrap();
+        }
+        assert_eq!([1, 1, 2], queries());
+
+        // Probed again after backing off
+        stack.clock.delay_ms(2000).await;
+        dns.get_host_by_name("a.example.com", AddrType::IPv4)
+            .await
+            .unwrap();
+        assert_eq!([2, 1, 3], queries());
+
+        let stats = dns.server_stats();
+        assert_eq!(server(1), stats[0].server);
+        assert_eq!(2, stats[0].failures);
+        assert_eq!(2, stats[0].consecutive_failures);
+        assert!(stats[0].backoff_until_ms.unwrap() > stack.clock.now_ms() + 1000);
+        assert!(matches!(stats[1].srtt_ms, Some(30..=32)));
+        assert!(matches!(stats[2].srtt_ms, Some(10..=12)));
+        assert_eq!(3, stats[2].queries);
+        assert_eq!(0, stats[2].failures);
+
+        // Reset, also for servers that stay
+        dns.reconfigure(&[server(3), server(4)], &[], false)
+            .unwrap();
+        let stats = dns.server_stats();
+        assert_eq!(ServerStats::new(server(3)), stats[0]);
+        assert_eq!(ServerStats::new(server(4)), stats[1]);
+    }
+
+    #[tokio::test]
+    async fn test_race() {
+        let stack = MockStack::new(vec![
+            Server::new(1, |_| Reply::Rcode(Rcode::SERVFAIL)),
+            Server::new(2, a([192, 0, 2, 20])).delay(50),
+            Server::new(3, a([192, 0, 2, 30])).delay(20),
+        ]);
+        let race = Strategy::Race {
+            fanout: 3,
+            stagger_ms: 0,
+        };
+        let mut dns = client(&stack, Config::new().cache_size(0).strategy(race));
+        dns.set_race_buffers(Box::leak(Box::new([Packet::new(), Packet::new()])));
+        // The error does not win, and the fastest valid response does
+        let ip = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 30)), ip.unwrap());
+        assert_eq!([1, 1, 1], [0, 1, 2].map(|i| stack.queries(i)));
+        assert!(stack.clock.now_ms() < 50);
+
+        // One server at a time without buffers
+        let stack = MockStack::new(vec![
+            Server::new(1, |_| Reply::Rcode(Rcode::SERVFAIL)),
+            Server::new(2, a([192, 0, 2, 20])).delay(50),
+            Server::new(3, a([192, 0, 2, 30])).delay(20),
+        ]);
+        let dns = client(&stack, Config::new().cache_size(0).strategy(race));
+        let ip = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 20)), ip.unwrap());
+        assert_eq!([1, 1, 0], [0, 1, 2].map(|i| stack.queries(i)));
+    }
+
+    #[tokio::test]
+    async fn test_race_stagger() {
+        let stack = MockStack::new(vec![
+            Server::new(1, a([192, 0, 2, 10])).delay(30),
+            Server::new(2, |_| Reply::Fail),
+            Server::new(3, a([192, 0, 2, 30])),
+        ]);
+        let queries = || [0, 1, 2].map(|i| stack.queries(i));
+        let race = Strategy::Race {
+            fanout: 2,
+            stagger_ms: 100,
+        };
+        let mut dns = client(&stack, Config::new().cache_size(0).strategy(race));
+        dns.set_race_buffers(Box::leak(Box::new([Packet::new()])));
+        let ip = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)), ip.unwrap());
+        assert_eq!([1, 0, 0], queries());
+
+        // Servers 2 and 3 were not used yet so they go first, and server 2
+        // fails at once, so server 3 is queried without waiting for the stagger
+        let start = stack.clock.now_ms();
+        let ip = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 30)), ip.unwrap());
+        assert_eq!([1, 1, 1], queries());
+        assert!(stack.clock.now_ms() - start < 100);
+    }
+
+    #[tokio::test]
+    async fn test_happy_eyeballs() {
+        let v6 = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let stack = MockStack::new(vec![Server::new(1, move |q| {
+            let aaaa = Reply::Records(vec![(QType::AAAA, v6.octets().to_vec())]);
+            match (q.qtype, q.qname.to_string().as_str()) {
+                (QType::A, "v6only.example.com") => Reply::Records(vec![]),
+                (QType::A, _) => Reply::Records(vec![
+                    (QType::A, vec![192, 0, 2, 1]),
+                    (QType::A, vec![192, 0, 2, 2]),
+                ]),
+                (QType::AAAA, "slow.example.com") => Reply::Delayed(100, Box::new(aaaa)),
+                (QType::AAAA, "v6only.example.com") => Reply::Delayed(100, Box::new(aaaa)),
+                _ => aaaa,
+            }
+        })
+        .delay(10)]);
+        let dns = client(&stack, Config::new().cache_size(0));
+        let v6 = IpAddr::V6(v6);
+        let v4 = |n| IpAddr::V4(Ipv4Addr::new(192, 0, 2, n));
+
+        let addrs = dns
+            .get_host_addrs("dual.example.com", AddrType::Either)
+            .await;
+        assert_eq!([v6, v4(1), v4(2)], *addrs.unwrap());
+        let ip = dns
+            .get_host_by_name("dual.example.com", AddrType::Either)
+            .await;
+        assert_eq!(v6, ip.unwrap());
+        let ip = dns
+            .get_host_by_name("dual.example.com", AddrType::IPv4)
+            .await;
+        assert_eq!(v4(1), ip.unwrap());
+
+        // AAAA records later than the resolution delay are left out
+        let start = stack.clock.now_ms();
+        let addrs = dns
+            .get_host_addrs("slow.example.com", AddrType::Either)
+            .await;
+        assert_eq!([v4(1), v4(2)], *addrs.unwrap());
+        assert!(stack.clock.now_ms() - start < 100);
+
+        // Unless there are no A records
+        let addrs = dns
+            .get_host_addrs("v6only.example.com", AddrType::Either)
+            .await;
+        assert_eq!([v6], *addrs.unwrap());
+        let result = dns
+            .get_host_by_name("v6only.example.com", AddrType::IPv4)
+            .await;
+        assert!(matches!(result, Err(Error::NoData { .. })));
+
+        let queries = stack.queries(0);
+        let addrs = dns.get_host_addrs("localhost", AddrType::Either).await;
+        let localhost = [
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+        ];
+        assert_eq!(localhost, *addrs.unwrap());
+        assert_eq!(queries, stack.queries(0));
+    }
+
+    fn srv(priority: u16, weight: u16, port: u16, target: &str) -> std::vec::Vec<u8> {
+        let mut rdata = [priority, weight, port].map(u16::to_be_bytes).concat();
+        rdata.extend_from_slice(DomainName::from_str(target).unwrap().as_bytes());
+        rdata
+    }
+
+    #[tokio::test]
+    async fn test_lookup_srv() {
+        let v6 = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let service = "_mqtt._tcp.example.net";
+        let stack = MockStack::new(vec![Server::new(1, move |q| {
+            match (q.qtype, q.qname.to_string().as_str()) {
+                (QType::SRV, "_mqtt._tcp.example.net") => Reply::Sections(
+                    vec![
+                        (service, QType::SRV, srv(20, 0, 8883, "backup.example.net")),
+                        (service, QType::SRV, srv(10, 0, 1883, "broker.example.net")),
+                        ("example.net", QType::SRV, srv(0, 0, 1, "example.net")),
+                    ],
+                    vec![
+                        ("broker.example.net", QType::A, vec![192, 0, 2, 1]),
+                        ("broker.example.net", QType::AAAA, v6.octets().to_vec()),
+                        ("example.net", QType::A, vec![192, 0, 2, 9]),
+                    ],
+                ),
+                (QType::SRV, "_imap._tcp.example.net") => {
+                    Reply::Records(vec![(QType::SRV, srv(0, 0, 0, "."))])
+                }
+                (QType::SRV, "_many._tcp.example.net") => Reply::Records(
+                    (0..MAX_SRV as u16)
+                        .map(|n| (QType::SRV, srv(10, 0, n, "backup.example.net")))
+                        .chain([(QType::SRV, srv(0, 0, 99, "broker.example.net"))])
+                        .collect(),
+                ),
+                (QType::SRV, _) => Reply::Rcode(Rcode::NXDOMAIN),
+                (QType::A, "backup.example.net") => {
+                    Reply::Records(vec![(QType::A, vec![192, 0, 2, 2])])
+                }
+                _ => Reply::Records(vec![]),
+            }
+        })]);
+        let dns = client(&stack, Config::new());
+        let v4 = |n| IpAddr::V4(Ipv4Addr::new(192, 0, 2, n));
+
+        let targets = dns.lookup_srv(service, AddrType::IPv4).await.unwrap();
+        assert_eq!(2, targets.len());
+        assert_eq!((10, 1883), (targets[0].priority, targets[0].port));
+        assert_eq!("broker.example.net", targets[0].target.to_string());
+        assert_eq!([v4(1)], *targets[0].addrs);
+        assert_eq!((20, 8883), (targets[1].priority, targets[1].port));
+        assert_eq!("backup.example.net", targets[1].target.to_string());
+        assert_eq!([v4(2)], *targets[1].addrs);
+        // The broker was not looked up
+        assert_eq!(2, stack.queries(0));
+
+        let targets = dns.lookup_srv(service, AddrType::Either).await.unwrap();
+        assert_eq!([IpAddr::V6(v6), v4(1)], *targets[0].addrs);
+        assert_eq!([v4(2)], *targets[1].addrs);
+
+        let targets = dns.lookup_srv("_imap._tcp.example.net", AddrType::Either);
+        assert!(targets.await.unwrap().is_empty());
+        // The target of the lowest priority is kept, even if it comes last
+        let targets = dns.lookup_srv("_many._tcp.example.net", AddrType::IPv4);
+        let targets = targets.await.unwrap();
+        assert_eq!(MAX_SRV, targets.len());
+        assert_eq!((0, 99), (targets[0].priority, targets[0].port));
+        assert!(targets[1..].iter().all(|t| t.priority == 10));
+        let result = dns.lookup_srv("_xmpp._tcp.example.net", AddrType::Either);
+        assert!(matches!(result.await, Err(Error::NxDomain { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_get_socket_addrs() {
+        let stack = MockStack::new(vec![Server::new(1, |q| {
+            match (q.qtype, q.qname.to_string().as_str()) {
+                (QType::SRV, "_mqtt._tcp.example.net") => Reply::Records(vec![
+                    (QType::SRV, srv(10, 0, 8883, "broker.example.net")),
+                    (QType::SRV, srv(20, 0, 1883, "backup.example.net")),
+                ]),
+                (QType::SRV, "_imap._tcp.example.net") => {
+                    Reply::Records(vec![(QType::SRV, srv(0, 0, 0, "."))])
+                }
+                (QType::A, "broker.example.net") => {
+                    Reply::Records(vec![(QType::A, vec![192, 0, 2, 1])])
+                }
+                (QType::A, "backup.example.net") => Reply::Records(vec![
+                    (QType::A, vec![192, 0, 2, 2]),
+                    (QType::A, vec![192, 0, 2, 3]),
+                ]),
+                _ => Reply::Records(vec![]),
+            }
+        })]);
+        let dns = client(&stack, Config::new());
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+
+        let addrs = dns.get_socket_addrs("broker.example.net:8883", AddrType::IPv4);
+        assert_eq!([addr("192.0.2.1:8883")], *addrs.await.unwrap());
+        let addrs = dns.get_socket_addrs("[2001:db8::1]:53", AddrType::Either);
+        assert_eq!([addr("[2001:db8::1]:53")], *addrs.await.unwrap());
+
+        let addrs = dns.get_socket_addrs("_mqtt._tcp.example.net", AddrType::IPv4);
+        let expected = [
+            addr("192.0.2.1:8883"),
+            addr("192.0.2.2:1883"),
+            addr("192.0.2.3:1883"),
+        ];
+        assert_eq!(expected, *addrs.await.unwrap());
+
+        let result = dns.get_socket_addrs("_imap._tcp.example.net", AddrType::IPv4);
+        assert!(matches!(result.await, Err(Error::NotFound)));
+        let result = dns.get_socket_addrs("broker.example.net", AddrType::IPv4);
+        assert!(matches!(result.await, Err(Error::InvalidAddress)));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_mx() {
+        let mx = |preference: u16, exchange: &str| {
+            let mut rdata = preference.to_be_bytes().to_vec();
+            rdata.extend_from_slice(DomainName::from_str(exchange).unwrap().as_bytes());
+            rdata
+        };
+        let stack = MockStack::new(vec![Server::new(1, move |q| {
+            match (q.qtype, q.qname.to_string().as_str()) {
+                (QType::MX, "example.net") => Reply::Sections(
+                    vec![
+                        ("example.net", QType::MX, mx(20, "backup.example.net")),
+                        ("example.net", QType::MX, mx(10, "mail.example.net")),
+                    ],
+                    vec![("mail.example.net", QType::A, vec![192, 0, 2, 1])],
+                ),
+                (QType::MX, "nomail.example.net") => Reply::Records(vec![(QType::MX, mx(0, "."))]),
+                (QType::MX, "many.example.net") => Reply::Records(
+                    (0..=MAX_MX as u16)
+                        .rev()
+                        .map(|n| (QType::MX, mx(n, "mail.example.net")))
+                        .collect(),
+                ),
+                (QType::A, "backup.example.net" | "host.example.net") => {
+                    Reply::Records(vec![(QType::A, vec![192, 0, 2, 2])])
+                }
+                (QType::MX, "missing.example.net") => Reply::Rcode(Rcode::NXDOMAIN),
+                _ => Reply::Records(vec![]),
+            }
+        })]);
+        let dns = client(&stack, Config::new());
+        let v4 = |n| IpAddr::V4(Ipv4Addr::new(192, 0, 2, n));
+
+        let exchanges = dns.lookup_mx("example.net", AddrType::IPv4).await.unwrap();
+        assert_eq!(2, exchanges.len());
+        assert_eq!(10, exchanges[0].preference);
+        assert_eq!("mail.example.net", exchanges[0].exchange.to_string());
+        assert_eq!([v4(1)], *exchanges[0].addrs);
+        assert_eq!("backup.example.net", exchanges[1].exchange.to_string());
+        assert_eq!([v4(2)], *exchanges[1].addrs);
+
+        // The implicit MX
+        let exchanges = dns.lookup_mx("host.example.net", AddrType::IPv4).await;
+        let exchanges = exchanges.unwrap();
+        assert_eq!(1, exchanges.len());
+        assert_eq!(0, exchanges[0].preference);
+        assert_eq!("host.example.net", exchanges[0].exchange.to_string());
+        assert_eq!([v4(2)], *exchanges[0].addrs);
+
+        let exchanges = dns.lookup_mx("nomail.example.net", AddrType::IPv4).await;
+        assert!(exchanges.unwrap().is_empty());
+        // The most preferred are kept, even if they come last
+        let exchanges = dns.lookup_mx("many.example.net", AddrType::IPv4).await;
+        let preferences: std::vec::Vec<_> =
+            exchanges.unwrap().iter().map(|mx| mx.preference).collect();
+        assert_eq!(
+            (0..MAX_MX as u16).collect::<std::vec::Vec<_>>(),
+            preferences
+        );
+        let result = dns.lookup_mx("other.example.net", AddrType::IPv4).await;
+        assert!(matches!(result, Err(Error::NoData { .. })));
+        let result = dns.lookup_mx("missing.example.net", AddrType::IPv4).await;
+        assert!(matches!(result, Err(Error::NxDomain { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_soa_ns() {
+        let name = |s: &str| DomainName::from_str(s).unwrap().as_bytes().to_vec();
+        let mut soa = [name("ns1.example.net"), name("hostmaster.example.net")].concat();
+        for n in [2024010101u32, 7200, 900, 1209600, 300] {
+            soa.extend_from_slice(&n.to_be_bytes());
+        }
+        let stack = MockStack::new(vec![Server::new(1, move |q| {
+            match (q.qtype, q.qname.to_string().as_str()) {
+                (QType::SOA, "example.net") => Reply::Records(vec![(QType::SOA, soa.clone())]),
+                (QType::NS, "example.net") => Reply::Sections(
+                    vec![
+                        ("example.net", QType::NS, name("ns1.example.net")),
+                        ("example.net", QType::NS, name("ns.example.org")),
+                    ],
+                    vec![
+                        ("ns1.example.net", QType::A, vec![192, 0, 2, 53]),
+                        (
+                            "ns1.example.net",
+                            QType::AAAA,
+                            vec![0x20, 1, 0xd, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x53],
+                        ),
+                    ],
+                ),
+                _ => Reply::Records(vec![]),
+            }
+        })]);
+        let dns = client(&stack, Config::new());
+
+        let mut packet = Packet::new();
+        let soa = dns.lookup_soa("example.net", &mut packet).await.unwrap();
+        assert_eq!(Domain::String("ns1.example.net"), soa.mname);
+        assert_eq!(Domain::String("hostmaster.example.net"), soa.rname);
+        assert_eq!(2024010101, soa.serial);
+        assert_eq!(300, soa.minimum);
+
+        let servers = dns
+            .lookup_ns("example.net", AddrType::Either)
+            .await
+            .unwrap();
+        assert_eq!(2, servers.len());
+        assert_eq!("ns1.example.net", servers[0].name.to_string());
+        let v6 = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53);
+        let v4 = Ipv4Addr::new(192, 0, 2, 53);
+        assert_eq!([IpAddr::V6(v6), IpAddr::V4(v4)], *servers[0].addrs);
+        assert_eq!("ns.example.org", servers[1].name.to_string());
+        assert!(servers[1].addrs.is_empty());
+
+        let mut packet = Packet::new();
+        let result = dns.lookup_soa("www.example.net", &mut packet).await;
+        assert!(matches!(result, Err(Error::NoData { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_https() {
+        let https = |priority: u16, target: &str, params: &[u8]| {
+            let mut rdata = priority.to_be_bytes().to_vec();
+            rdata.extend_from_slice(DomainName::from_str(target).unwrap().as_bytes());
+            rdata.extend_from_slice(params);
+            (QType::HTTPS, rdata)
+        };
+        let stack = MockStack::new(vec![Server::new(1, move |q| {
+            match (q.qtype, q.qname.to_string().as_str()) {
+                (QType::HTTPS, "example.com") => {
+                    Reply::Records(vec![https(0, "cdn.example.net", b"")])
+                }
+                (QType::HTTPS, "cdn.example.net") => Reply::Records(vec![
+                    https(2, ".", b"\x00\x01\x00\x03\x02h2"),
+                    https(1, ".", b"\x00\x01\x00\x03\x02h3\x00\x03\x00\x02\x20\xfb"),
+                ]),
+                (QType::HTTPS, "loop.example.com") => {
+                    Reply::Records(vec![https(0, "loop.example.com", b"")])
+                }
+                (QType::HTTPS, "gone.example.com") => Reply::Records(vec![https(0, ".", b"")]),
+                _ => Reply::Records(vec![]),
+            }
+        })]);
+        let dns = client(&stack, Config::new());
+
+        let mut packet = Packet::new();
+        let records = dns.lookup_https("example.com", &mut packet).await.unwrap();
+        assert_eq!("cdn.example.net", records.name().to_string());
+        let records: std::vec::Vec<Https<'_>> = records.map(Result::unwrap).collect();
+        assert_eq!(2, records.len());
+        assert_eq!(1, records[1].priority);
+        assert_eq!(Some(8443), records[1].port());
+        let alpn: std::vec::Vec<&[u8]> = records[1].alpn().unwrap().collect();
+        assert_eq!([b"h3"], alpn[..]);
+        assert_eq!(None, records[0].port());
+        assert_eq!(2, stack.queries(0));
+
+        let mut packet = Packet::new();
+        let result = dns.lookup_https("loop.example.com", &mut packet).await;
+        assert!(matches!(result, Err(Error::NotFound)));
+        assert_eq!(2 + MAX_ALIASES + 1, stack.queries(0));
+        let mut packet = Packet::new();
+        let result = dns.lookup_https("gone.example.com", &mut packet).await;
+        assert!(matches!(result, Err(Error::NoData { .. })));
+        let mut packet = Packet::new();
+        let result = dns.lookup_https("other.example.com", &mut packet).await;
+        assert!(matches!(result, Err(Error::NoData { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_tlsa() {
+        let stack = MockStack::new(vec![Server::new(1, |q| {
+            let tlsa = Reply::Records(vec![(QType::TLSA, vec![3, 1, 1, 0xab, 0xcd])]);
+            match (q.qtype, q.qname.to_string().as_str()) {
+                (QType::TLSA, "_443._tcp.example.com") => Reply::Authentic(Box::new(tlsa)),
+                (QType::TLSA, "_8883._tcp.example.com") => tlsa,
+                _ => Reply::Records(vec![]),
+            }
+        })]);
+        let dns = client(&stack, Config::new());
+
+        let mut packet = Packet::new();
+        let mut records = dns
+            .lookup_tlsa(443, "tcp", "example.com", &mut packet)
+            .await
+            .unwrap();
+        assert!(records.authentic_data());
+        let tlsa = records.next().unwrap().unwrap();
+        assert_eq!((3, 1, 1), (tlsa.usage, tlsa.selector, tlsa.matching_type));
+        assert_eq!([0xab, 0xcd], tlsa.data);
+        assert!(records.next().is_none());
+
+        let mut packet = Packet::new();
+        let records = dns
+            .lookup_tlsa(8883, "tcp", "example.com", &mut packet)
+            .await;
+        assert!(!records.unwrap().authentic_data());
+        let mut packet = Packet::new();
+        let result = dns
+            .lookup_tlsa(443, "udp", "example.com", &mut packet)
+            .await;
+        assert!(matches!(result, Err(Error::NoData { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_txt() {
+        let stack = MockStack::new(vec![Server::new(1, |q| {
+            match (q.qtype, q.qname.to_string().as_str()) {
+                (QType::TXT, "device.example.net") => Reply::Records(vec![
+                    (
+                        QType::TXT,
+                        b"\x0dbroker=mqtt.e\x0dxample.net:88\x0283".to_vec(),
+                    ),
+                    (QType::TXT, b"\x09version=2\x03ota".to_vec()),
+                    (QType::A, vec![192, 0, 2, 1]),
+                ]),
+                _ => Reply::Records(vec![]),
+            }
+        })]);
+        let dns = client(&stack, Config::new());
+
+        let mut packet = Packet::new();
+        let records = dns.lookup_txt("device.example.net", &mut packet).await;
+        let records: std::vec::Vec<Txt<'_>> = records.unwrap().map(Result::unwrap).collect();
+        assert_eq!(2, records.len());
+        let mut buf = [0; 64];
+        let value = records[0].value(&mut buf).unwrap();
+        let broker = Attribute::parse(value).unwrap();
+        assert!(broker.key_eq("broker"));
+        assert_eq!(Some(&b"mqtt.example.net:8883"[..]), broker.value());
+        let mut attributes = records[1].attributes();
+        assert!(attributes.next().unwrap().key_eq("version"));
+        assert_eq!(None, attributes.next().unwrap().value());
+
+        let mut packet = Packet::new();
+        let result = dns.lookup_txt("other.example.net", &mut packet).await;
+        assert!(matches!(result, Err(Error::NoData { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_no_failover_on_nxdomain() {
+        let stack = MockStack::new(vec![
+            Server::new(1, |_| Reply::Rcode(Rcode::NXDOMAIN)),
+            Server::new(2, a([192, 0, 2, 10])),
+        ]);
+        let dns = client(&stack, Config::new());
+        let result = dns.get_host_by_name("example.com.", AddrType::IPv4).await;
+        assert!(matches!(result, Err(Error::NxDomain { server }) if server == mock::server(1)));
+        assert_eq!(0, stack.queries(1));
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure() {
+        let stack = MockStack::new(vec![
+            Server::new(1, a([192, 0, 2, 10])),
+            Server::new(2, |q| {
+                match q.qname == Domain::String("broker.site1.example.net") {
+                    true => a([192, 0, 2, 20])(q),
+                    false => Reply::Rcode(Rcode::NXDOMAIN),
+                }
+            }),
+        ]);
+        let dns = client(&stack, Config::new().servers(&[]));
+        dns.reconfigure(&[server(1)], &[], false).unwrap();
+        let ip = dns.get_host_by_name("broker", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)), ip.unwrap());
+
+        dns.reconfigure(&[server(2)], &["site1.example.net"], false)
+            .unwrap();
+        assert_eq!([server(2)], dns.servers()[..]);
+        assert_eq!(1, dns.search().len());
+
+        // Still cached from the previous server
+        let ip = dns.get_host_by_name("broker.", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)), ip.unwrap());
+
+        dns.reconfigure(&[server(2)], &["site1.example.net"], true)
+            .unwrap();
+        let ip = dns.get_host_by_name("broker", AddrType::IPv4).await;
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 20)), ip.unwrap());
+        assert_eq!(1, stack.queries(0));
+
+        assert_eq!(
+            Err(ConfigError::NoServers),
+            dns.reconfigure(&[], &["site1.example.net"], true)
+        );
+
+        let mut config = NetworkConfig::new();
+        config
+            .add_dhcp_option(DHCP_DNS_SERVERS, &[192, 0, 2, 1])
+            .unwrap();
+        config
+            .add_dhcp_option(DHCP_DOMAIN_NAME, b"example.net")
+            .unwrap();
+        dns.apply(&config, true).unwrap();
+        assert_eq!([server(1)], dns.servers()[..]);
+        assert_eq!(config.search(), &dns.search()[..]);
+        assert_eq!(
+            Err(ConfigError::NoServers),
+            dns.apply(&NetworkConfig::new(), true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let stack = MockStack::new(vec![
+            Server::new(1, |_| Reply::Silent),
+            Server::new(2, |_| Reply::Fail),
+            Server::new(3, a([192, 0, 2, 10])).delay(500),
+        ]);
+        let dns = client(&stack, Config::new().timeout_ms(100).attempts(3));
+        let result = dns.get_host_by_name("example.com", AddrType::IPv4).await;
+        assert!(matches!(result, Err(Error::Timeout { server }) if server == mock::server(3)));
+        assert_eq!([3, 3, 3], [0, 1, 2].map(|i| stack.queries(i)));
+    }
+
+    #[test]
+    fn test_config() {
+        let stack = MockStack::new(vec![]);
+        let servers = [server(1); MAX_SERVERS + 1];
+        let config =
+            |config: Config<'_>| ItsDns::with_config(&stack, NoClock, &config).err().unwrap();
+        assert_eq!(ConfigError::NoServers, config(Config::new()));
+        assert_eq!(
+            ConfigError::TooManyServers,
+            config(Config::new().servers(&servers))
+        );
+
+        let config = |c: Config<'_>| config(c.servers(&servers[..1]));
+        assert_eq!(ConfigError::NoAttempts, config(Config::new().attempts(0)));
+        assert_eq!(
+            ConfigError::CacheTooLarge,
+            config(Config::new().cache_size(MAX_CACHE + 1))
+        );
+        assert_eq!(
+            ConfigError::InvalidPayloadSize,
+            config(Config::new().edns_payload_size(Some(511)))
+        );
+        assert_eq!(
+            ConfigError::InvalidFanout,
+            config(Config::new().strategy(Strategy::Race {
+                fanout: 0,
+                stagger_ms: 0
+            }))
+        );
+        assert_eq!(
+            ConfigError::TooManySearchDomains,
+            config(Config::new().search(&["example.com"; MAX_SEARCH + 1]))
+        );
+    }
+
+    #[test]
+    fn test_reverse_name() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10));
+        assert_eq!("10.2.0.192.in-addr.arpa", reverse_name(&v4).as_str());
+
+        let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x1ab));
+        assert_eq!(
+            "b.a.1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa",
+            reverse_name(&v6).as_str()
+        );
+    }
+
+    #[test]
+    fn test_parse_ip_literal() {
+        assert_eq!(
+            Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+            parse_ip_literal("192.168.1.10")
+        );
+        let v6 = Some(IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)));
+        assert_eq!(v6, parse_ip_literal("fe80::1"));
+        assert_eq!(v6, parse_ip_literal("[fe80::1]"));
+        assert_eq!(v6, parse_ip_literal("fe80::1%eth0"));
+        assert_eq!(v6, parse_ip_literal("[fe80::1%25eth0]"));
+
+        assert_eq!(None, parse_ip_literal("example.com"));
+        assert_eq!(None, parse_ip_literal("[192.168.1.10"));
+        assert_eq!(None, parse_ip_literal("[192.168.1.10]"));
+        assert_eq!(None, parse_ip_literal("fe80::1%"));
+        assert_eq!(None, parse_ip_literal("192.168.1.10%eth0"));
+        assert_eq!(None, parse_ip_literal("1.2.3"));
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let host = |host, port| Some(Target::Host(host, port));
+        assert_eq!(host("example.com", 8883), parse_target("example.com:8883"));
+        assert_eq!(host("192.0.2.1", 53), parse_target("192.0.2.1:53"));
+        assert_eq!(host("[2001:db8::1]", 53), parse_target("[2001:db8::1]:53"));
+        assert_eq!(host("[fe80::1%eth0]", 0), parse_target("[fe80::1%eth0]:0"));
+        let service = Some(Target::Service("_mqtt._tcp.example.net"));
+        assert_eq!(service, parse_target("_mqtt._tcp.example.net"));
+
+        assert_eq!(None, parse_target("example.com"));
+        assert_eq!(None, parse_target("example.com:"));
+        assert_eq!(None, parse_target("example.com:+80"));
+        assert_eq!(None, parse_target("example.com:65536"));
+        assert_eq!(None, parse_target(":80"));
+        assert_eq!(None, parse_target("2001:db8::1"));
+        assert_eq!(None, parse_target("2001:db8::1:53"));
+        assert_eq!(None, parse_target("[192.0.2.1]:53"));
+        assert_eq!(None, parse_target("[example.com]:53"));
     }
 }
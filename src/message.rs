@@ -1,146 +1,306 @@
 #![allow(dead_code)]
-use crate::DnsError;
+use crate::{DecodeError, DecodeReason, DnsError, DomainError, Rcode};
+use core::cmp::Ordering;
+use core::fmt::{self, Write};
+use core::hash::{Hash, Hasher};
+use heapless::Vec;
 
+/// Kind of query in a message.
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) enum Opcode {
+pub enum Opcode {
+    /// A standard query.
     Query,
+    /// An inverse query (obsolete).
     IQuery,
+    /// A server status request.
     Status,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-#[repr(u8)]
-pub(crate) enum QType {
-    A = 1,
-    NS = 2,
-    MD = 3,
-    MF = 4,
-    CNAME = 5,
-    SOA = 6,
-    MB = 7,
-    MG = 8,
-    MR = 9,
-    NULL = 10,
-    WKS = 11,
-    PTR = 12,
-    HINFO = 13,
-    MINFO = 14,
-    MX = 15,
-    TXT = 16,
-    AXFR = 252,
-    MAILB = 253,
-    MAILA = 254,
-    ALL = 255,
-}
-
-impl TryFrom<u16> for QType {
-    type Error = DnsError;
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
+/// Type of a record or question.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QType {
+    /// A host address.
+    A,
+    /// An authoritative name server.
+    NS,
+    /// A mail destination (obsolete).
+    MD,
+    /// A mail forwarder (obsolete).
+    MF,
+    /// The canonical name for an alias.
+    CNAME,
+    /// Marks the start of a zone of authority.
+    SOA,
+    /// A mailbox domain name.
+    MB,
+    /// A mail group member.
+    MG,
+    /// A mail rename domain name.
+    MR,
+    /// A null record.
+    NULL,
+    /// A well known service description.
+    WKS,
+    /// A domain name pointer.
+    PTR,
+    /// Host information.
+    HINFO,
+    /// Mailbox or mail list information.
+    MINFO,
+    /// Mail exchange.
+    MX,
+    /// Text strings.
+    TXT,
+    /// An IPv6 host address (RFC 3596).
+    AAAA,
+    /// The location of a service (RFC 2782).
+    SRV,
+    /// An EDNS pseudo-record (RFC 6891).
+    OPT,
+    /// A TLS certificate association (RFC 6698).
+    TLSA,
+    /// A general service binding (RFC 9460).
+    SVCB,
+    /// A service binding for HTTPS (RFC 9460).
+    HTTPS,
+    /// A request for a transfer of an entire zone.
+    AXFR,
+    /// A request for mailbox-related records.
+    MAILB,
+    /// A request for mail agent records (obsolete).
+    MAILA,
+    /// A request for all records.
+    ALL,
+    /// A type without a name in this crate.
+    Unknown(u16),
+}
+
+impl From<u16> for QType {
+    fn from(value: u16) -> Self {
         match value {
-            1 => Ok(Self::A),
-            2 => Ok(Self::NS),
-            3 => Ok(Self::MD),
-            4 => Ok(Self::MF),
-            5 => Ok(Self::CNAME),
-            6 => Ok(Self::SOA),
-            7 => Ok(Self::MB),
-            8 => Ok(Self::MG),
-            9 => Ok(Self::MR),
-            10 => Ok(Self::NULL),
-            11 => Ok(Self::WKS),
-            12 => Ok(Self::PTR),
-            13 => Ok(Self::HINFO),
-            14 => Ok(Self::MINFO),
-            15 => Ok(Self::MX),
-            16 => Ok(Self::TXT),
-            252 => Ok(Self::AXFR),
-            253 => Ok(Self::MAILB),
-            254 => Ok(Self::MAILA),
-            255 => Ok(Self::ALL),
-            _ => Err(DnsError::Decode),
-        }
-    }
-}
-
-#[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) enum QClass {
-    IN = 1,
-    CS = 2,
-    CH = 3,
-    HS = 4,
+            1 => Self::A,
+            2 => Self::NS,
+            3 => Self::MD,
+            4 => Self::MF,
+            5 => Self::CNAME,
+            6 => Self::SOA,
+            7 => Self::MB,
+            8 => Self::MG,
+            9 => Self::MR,
+            10 => Self::NULL,
+            11 => Self::WKS,
+            12 => Self::PTR,
+            13 => Self::HINFO,
+            14 => Self::MINFO,
+            15 => Self::MX,
+            16 => Self::TXT,
+            28 => Self::AAAA,
+            33 => Self::SRV,
+            41 => Self::OPT,
+            52 => Self::TLSA,
+            64 => Self::SVCB,
+            65 => Self::HTTPS,
+            252 => Self::AXFR,
+            253 => Self::MAILB,
+            254 => Self::MAILA,
+            255 => Self::ALL,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<QType> for u16 {
+    fn from(value: QType) -> Self {
+        match value {
+            QType::A => 1,
+            QType::NS => 2,
+            QType::MD => 3,
+            QType::MF => 4,
+            QType::CNAME => 5,
+            QType::SOA => 6,
+            QType::MB => 7,
+            QType::MG => 8,
+            QType::MR => 9,
+            QType::NULL => 10,
+            QType::WKS => 11,
+            QType::PTR => 12,
+            QType::HINFO => 13,
+            QType::MINFO => 14,
+            QType::MX => 15,
+            QType::TXT => 16,
+            QType::AAAA => 28,
+            QType::SRV => 33,
+            QType::OPT => 41,
+            QType::TLSA => 52,
+            QType::SVCB => 64,
+            QType::HTTPS => 65,
+            QType::AXFR => 252,
+            QType::MAILB => 253,
+            QType::MAILA => 254,
+            QType::ALL => 255,
+            QType::Unknown(value) => value,
+        }
+    }
+}
+
+/// Class of a record or question.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QClass {
+    /// The Internet.
+    IN,
+    /// The CSNET class (obsolete).
+    CS,
+    /// The CHAOS class.
+    CH,
+    /// Hesiod.
+    HS,
+    /// A class without a name in this crate, such as the payload size of an OPT record.
+    Unknown(u16),
 }
 
-impl TryFrom<u16> for QClass {
-    type Error = DnsError;
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
+impl From<u16> for QClass {
+    fn from(value: u16) -> Self {
         match value {
-            1 => Ok(Self::IN),
-            2 => Ok(Self::CS),
-            3 => Ok(Self::CH),
-            4 => Ok(Self::HS),
-            _ => Err(DnsError::Decode),
+            1 => Self::IN,
+            2 => Self::CS,
+            3 => Self::CH,
+            4 => Self::HS,
+            value => Self::Unknown(value),
         }
     }
 }
 
+impl From<QClass> for u16 {
+    fn from(value: QClass) -> Self {
+        match value {
+            QClass::IN => 1,
+            QClass::CS => 2,
+            QClass::CH => 3,
+            QClass::HS => 4,
+            QClass::Unknown(value) => value,
+        }
+    }
+}
+
+/// A DNS message, either built for encoding or decoded from a buffer.
 #[derive(Clone, Debug, Copy)]
-pub(crate) struct DnsMessage<'a> {
-    pub(crate) id: u16,
-    pub(crate) opcode: Opcode,
-    pub(crate) questions: Questions<'a>,
-    pub(crate) answers: Answers<'a>,
+pub struct DnsMessage<'a> {
+    /// Identifier used to match responses to queries.
+    pub id: u16,
+    /// Kind of query.
+    pub opcode: Opcode,
+    /// Header flags.
+    pub flags: Flags,
+    /// Response code, including the extended bits from the EDNS OPT record.
+    pub rcode: Rcode,
+    /// The question section.
+    pub questions: Questions<'a>,
+    /// The answer section.
+    pub answers: Answers<'a>,
+    /// The authority section.
+    pub authorities: Answers<'a>,
+    /// The additional section, not including the EDNS OPT record when encoding.
+    pub additionals: Answers<'a>,
+    /// EDNS parameters carried in an OPT record (RFC 6891).
+    pub edns: Option<Edns>,
 }
 
+/// Flags in the message header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flags {
+    /// The message is a response.
+    pub response: bool,
+    /// The responding server is an authority for the name.
+    pub authoritative: bool,
+    /// The message was truncated to fit the transport.
+    pub truncated: bool,
+    /// Recursive resolution is requested.
+    pub recursion_desired: bool,
+    /// The server supports recursive resolution.
+    pub recursion_available: bool,
+    /// All answer and authority data was authenticated by the server (RFC 4035).
+    pub authentic_data: bool,
+    /// DNSSEC validation is disabled (RFC 4035).
+    pub checking_disabled: bool,
+}
+
+/// EDNS parameters (RFC 6891).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edns {
+    /// Largest UDP payload the sender can receive.
+    pub udp_payload_size: u16,
+    /// EDNS version.
+    pub version: u8,
+    /// DNSSEC records are requested (RFC 3225).
+    pub dnssec_ok: bool,
+}
+
+/// The question section of a message.
 #[derive(Clone, Debug, Copy)]
-pub(crate) enum Questions<'a> {
+pub enum Questions<'a> {
+    /// Questions to be encoded.
     Slice(&'a [Question<'a>]),
+    /// Questions decoded from a message.
     Raw {
+        /// The whole message.
         message: &'a [u8],
+        /// Number of questions.
         count: usize,
+        /// The encoded questions.
         data: &'a [u8],
     },
 }
 
+/// A section of resource records in a message.
 #[derive(Clone, Debug, Copy)]
-pub(crate) enum Answers<'a> {
+pub enum Answers<'a> {
+    /// Records to be encoded.
     Slice(&'a [Answer<'a>]),
+    /// Records decoded from a message.
     Raw {
+        /// The whole message.
         message: &'a [u8],
+        /// Number of records.
         count: usize,
+        /// The encoded records.
         data: &'a [u8],
     },
 }
 
+/// A question for a name and record type.
 #[derive(Clone, Debug, Copy)]
-pub(crate) struct Question<'a> {
-    pub(crate) qname: Domain<'a>,
-    pub(crate) qtype: QType,
-    pub(crate) qclass: QClass,
+pub struct Question<'a> {
+    /// The name asked for.
+    pub qname: Domain<'a>,
+    /// The record type asked for.
+    pub qtype: QType,
+    /// The class asked for.
+    pub qclass: QClass,
 }
 
+/// A resource record.
 #[derive(Clone, Debug, Copy)]
-pub(crate) struct Answer<'a> {
-    pub(crate) domain: Domain<'a>,
-    pub(crate) r#type: QType,
-    pub(crate) class: QClass,
-    pub(crate) ttl: u32,
-    pub(crate) rdata: &'a [u8],
+pub struct Answer<'a> {
+    /// The owner name of the record.
+    pub domain: Domain<'a>,
+    /// The record type.
+    pub r#type: QType,
+    /// The record class.
+    pub class: QClass,
+    /// Number of seconds the record may be cached.
+    pub ttl: u32,
+    /// The record data.
+    pub rdata: &'a [u8],
 }
 
 impl<'a> Question<'a> {
     fn decode(data: &'a [u8], message: &'a [u8]) -> Result<(usize, Question<'a>), DnsError> {
-        let mut pos = 0;
-        let (p, qname) = Domain::decode(&data[pos..], message)?;
-        pos += p;
-
-        let qtype = u16::from_be_bytes([data[pos], data[pos + 1]]).try_into()?;
-        pos += 2;
-
-        let qclass = u16::from_be_bytes([data[pos], data[pos + 1]]).try_into()?;
-        pos += 2;
+        let mut r = Reader::new(data, message);
+        let qname = r.name()?;
+        let qtype = r.u16()?.into();
+        let qclass = r.u16()?.into();
         Ok((
-            pos,
+            r.pos,
             Question {
                 qname,
                 qtype,
@@ -149,45 +309,25 @@ impl<'a> Question<'a> {
         ))
     }
 
-    fn encode(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
-        let mut pos = 0;
-        pos += self.qname.encode(&mut buf[pos..])?;
-
-        let qtype = (self.qtype as u16).to_be_bytes();
-        buf[pos] = qtype[0];
-        buf[pos + 1] = qtype[1];
-        pos += 2;
-
-        let qclass = (self.qclass as u16).to_be_bytes();
-        buf[pos] = qclass[0];
-        buf[pos + 1] = qclass[1];
-        pos += 2;
-        Ok(pos)
+    fn encode(&self, enc: &mut Encoder<'_>) -> Result<(), DnsError> {
+        enc.put_name(&self.qname)?;
+        enc.put_u16(self.qtype.into())?;
+        enc.put_u16(self.qclass.into())?;
+        Ok(())
     }
 }
 
 impl<'a> Answer<'a> {
     fn decode(data: &'a [u8], message: &'a [u8]) -> Result<(usize, Answer<'a>), DnsError> {
-        let mut pos = 0;
-        let (p, domain) = Domain::decode(&data[pos..], message)?;
-        pos += p;
-
-        let r#type = u16::from_be_bytes([data[pos], data[pos + 1]]).try_into()?;
-        pos += 2;
-
-        let class = u16::from_be_bytes([data[pos], data[pos + 1]]).try_into()?;
-        pos += 2;
-
-        let ttl = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
-        pos += 4;
-
-        let rdata_len = u16::from_be_bytes([data[pos], data[pos + 1]]) as usize;
-        pos += 2;
-
-        let rdata = &data[pos..pos + rdata_len];
-
+        let mut r = Reader::new(data, message);
+        let domain = r.name()?;
+        let r#type = r.u16()?.into();
+        let class = r.u16()?.into();
+        let ttl = r.u32()?;
+        let rdata_len = r.u16()? as usize;
+        let rdata = r.slice(rdata_len)?;
         Ok((
-            pos,
+            r.pos,
             Answer {
                 domain,
                 r#type,
@@ -198,34 +338,21 @@ impl<'a> Answer<'a> {
         ))
     }
 
-    fn encode(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
-        let mut pos = 0;
-        pos += self.domain.encode(&mut buf[pos..])?;
-
-        let qtype = (self.r#type as u16).to_be_bytes();
-        buf[pos] = qtype[0];
-        buf[pos + 1] = qtype[1];
-        pos += 2;
-
-        let qclass = (self.class as u16).to_be_bytes();
-        buf[pos] = qclass[0];
-        buf[pos + 1] = qclass[1];
-        pos += 2;
-
-        buf[pos..pos + 4].copy_from_slice(&self.ttl.to_be_bytes());
-        pos += 4;
-
-        buf[pos..pos + 2].copy_from_slice(&(self.rdata.len() as u16).to_be_bytes());
-        pos += 2;
-
-        buf[pos..pos + self.rdata.len()].copy_from_slice(self.rdata);
-        pos += self.rdata.len();
-        Ok(pos)
+    fn encode(&self, enc: &mut Encoder<'_>) -> Result<(), DnsError> {
+        enc.put_name(&self.domain)?;
+        enc.put_u16(self.r#type.into())?;
+        enc.put_u16(self.class.into())?;
+        enc.put_u32(self.ttl)?;
+        let len = u16::try_from(self.rdata.len()).map_err(|_| DnsError::Encode)?;
+        enc.put_u16(len)?;
+        enc.put_slice(self.rdata)?;
+        Ok(())
     }
 }
 
 impl<'a> Questions<'a> {
-    fn count(&self) -> usize {
+    /// Number of questions.
+    pub fn count(&self) -> usize {
         match self {
             Questions::Slice(q) => q.len(),
             Questions::Raw {
@@ -236,47 +363,39 @@ impl<'a> Questions<'a> {
         }
     }
 
-    fn get(&'a self, i: usize) -> Result<Option<Question<'a>>, DnsError> {
-        match self {
-            Self::Slice(qs) => Ok(qs.get(i).copied()),
+    /// Get the question at index `i`.
+    ///
+    /// Questions are decoded from the start of the section on each call, use
+    /// [`Questions::iter`] to visit all questions.
+    pub fn get(&self, i: usize) -> Result<Option<Question<'a>>, DnsError> {
+        self.iter().nth(i).transpose()
+    }
+
+    /// Iterate over the questions, decoding each question once.
+    pub fn iter(&self) -> QuestionIter<'a> {
+        match *self {
+            Self::Slice(questions) => QuestionIter {
+                inner: SectionIter::Slice(questions.iter()),
+            },
             Self::Raw {
                 count,
                 data,
                 message,
-            } => {
-                if i < *count {
-                    let mut pos = 0;
-                    for question in 0..*count {
-                        let (p, q) = Question::decode(&data[pos..], message)?;
-                        if question == i {
-                            return Ok(Some(q));
-                        }
-                        pos += p;
-                    }
-                }
-                Ok(None)
-            }
+            } => QuestionIter {
+                inner: SectionIter::Raw {
+                    message,
+                    data,
+                    remaining: count,
+                },
+            },
         }
     }
 
-    fn encode(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
-        let mut pos = 0;
-        match self {
-            Questions::Slice(questions) => {
-                for question in questions.iter() {
-                    pos += question.encode(&mut buf[pos..])?;
-                }
-                Ok(pos)
-            }
-            Questions::Raw {
-                count: _,
-                message: _,
-                data,
-            } => {
-                buf[0..data.len()].copy_from_slice(data);
-                Ok(data.len())
-            }
+    fn encode(&self, enc: &mut Encoder<'_>) -> Result<(), DnsError> {
+        for question in self.iter() {
+            question?.encode(enc)?;
         }
+        Ok(())
     }
 
     fn decode(
@@ -286,14 +405,8 @@ impl<'a> Questions<'a> {
     ) -> Result<(usize, Questions<'a>), DnsError> {
         let mut pos = 0;
         for _question in 0..count {
-            let (p, _domain) = Domain::decode(&buf[pos..], message)?;
+            let (p, _) = Question::decode(&buf[pos..], message)?;
             pos += p;
-
-            let _qtype = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
-            pos += 2;
-
-            let _qclass = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
-            pos += 2;
         }
 
         Ok((
@@ -308,6 +421,7 @@ impl<'a> Questions<'a> {
 }
 
 impl<'a> Answers<'a> {
+    /// Number of records.
     pub fn count(&self) -> usize {
         match self {
             Answers::Slice(q) => q.len(),
@@ -319,47 +433,41 @@ impl<'a> Answers<'a> {
         }
     }
 
-    pub fn get(&'a self, i: usize) -> Result<Option<Answer<'a>>, DnsError> {
-        match self {
-            Self::Slice(qs) => Ok(qs.get(i).copied()),
+    /// Get the record at index `i`.
+    ///
+    /// Records are decoded from the start of the section on each call, use
+    /// [`Answers::iter`] to visit all records.
+    pub fn get(&self, i: usize) -> Result<Option<Answer<'a>>, DnsError> {
+        self.iter().nth(i).transpose()
+    }
+
+    /// Iterate over the records, decoding each record once.
+    pub fn iter(&self) -> AnswerIter<'a> {
+        match *self {
+            Self::Slice(answers) => AnswerIter {
+                inner: SectionIter::Slice(answers.iter()),
+            },
             Self::Raw {
                 count,
                 data,
                 message,
-            } => {
-                if i < *count {
-                    let mut pos = 0;
-                    for answer in 0..*count {
-                        let (p, a) = Answer::decode(&data[pos..], message)?;
-                        if answer == i {
-                            return Ok(Some(a));
-                        }
-                        pos += p;
-                    }
-                }
-                Ok(None)
-            }
+            } => AnswerIter {
+                inner: SectionIter::Raw {
+                    message,
+                    data,
+                    remaining: count,
+                },
+            },
         }
     }
 
-    fn encode(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
-        let mut pos = 0;
-        match self {
-            Answers::Slice(answers) => {
-                for answer in answers.iter() {
-                    pos += answer.encode(&mut buf[pos..])?;
-                }
-                Ok(pos)
-            }
-            Answers::Raw {
-                count: _,
-                message: _,
-                data,
-            } => {
-                buf[0..data.len()].copy_from_slice(data);
-                Ok(data.len())
-            }
+    fn encode(&self, enc: &mut Encoder<'_>) -> Result<(), DnsError> {
+        // Records are encoded one by one, as decoded records may contain
+        // compression pointers into their original message.
+        for answer in self.iter() {
+            answer?.encode(enc)?;
         }
+        Ok(())
     }
 
     fn decode(
@@ -369,22 +477,8 @@ impl<'a> Answers<'a> {
     ) -> Result<(usize, Answers<'a>), DnsError> {
         let mut pos = 0;
         for _answer in 0..count {
-            let (p, _) = Domain::decode(&buf[pos..], message)?;
+            let (p, _) = Answer::decode(&buf[pos..], message)?;
             pos += p;
-
-            let _qtype = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
-            pos += 2;
-
-            let _qclass = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
-            pos += 2;
-
-            let _ttl = u32::from_be_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]]);
-            pos += 4;
-
-            let rdata_len = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
-            pos += 2;
-
-            pos += rdata_len as usize;
         }
         Ok((
             pos,
@@ -397,79 +491,314 @@ impl<'a> Answers<'a> {
     }
 }
 
+/// Cursor over the questions of a message.
+pub struct QuestionIter<'a> {
+    inner: SectionIter<'a, Question<'a>>,
+}
+
+impl<'a> Iterator for QuestionIter<'a> {
+    type Item = Result<Question<'a>, DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_with(Question::decode)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Cursor over the records of a message section.
+#[derive(Clone)]
+pub struct AnswerIter<'a> {
+    inner: SectionIter<'a, Answer<'a>>,
+}
+
+#[derive(Clone)]
+enum SectionIter<'a, T> {
+    Slice(core::slice::Iter<'a, T>),
+    Raw {
+        message: &'a [u8],
+        data: &'a [u8],
+        remaining: usize,
+    },
+}
+
+impl<'a> Iterator for AnswerIter<'a> {
+    type Item = Result<Answer<'a>, DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_with(Answer::decode)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Decodes an item from the start of the data, returning the number of octets used.
+type DecodeFn<'a, T> = fn(&'a [u8], &'a [u8]) -> Result<(usize, T), DnsError>;
+
+impl<'a, T: Copy> SectionIter<'a, T> {
+    fn next_with(&mut self, decode: DecodeFn<'a, T>) -> Option<Result<T, DnsError>> {
+        match self {
+            Self::Slice(it) => it.next().copied().map(Ok),
+            Self::Raw {
+                message,
+                data,
+                remaining,
+            } => {
+                if *remaining == 0 {
+                    return None;
+                }
+                match decode(data, message) {
+                    Ok((p, item)) => {
+                        *data = &data[p..];
+                        *remaining -= 1;
+                        Some(Ok(item))
+                    }
+                    Err(e) => {
+                        *remaining = 0;
+                        Some(Err(e))
+                    }
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Self::Slice(it) => it.size_hint(),
+            Self::Raw { remaining, .. } => (0, Some(*remaining)),
+        }
+    }
+}
+
+/// A borrowed domain name.
 #[derive(Clone, Debug, Copy)]
-pub(crate) enum Domain<'a> {
+pub enum Domain<'a> {
+    /// A name in presentation format, such as `example.com`.
     String(&'a str),
-    Raw { data: &'a [u8], message: &'a [u8] },
+    /// A name in wire format within a DNS message, which may use compression pointers.
+    Raw {
+        /// The encoded name.
+        data: &'a [u8],
+        /// The message that compression pointers refer to.
+        message: &'a [u8],
+    },
 }
 
-struct DomainIter<'a> {
-    domain: &'a Domain<'a>,
-    pos: usize,
-    len: usize,
-    ptr: Option<usize>,
-    first: bool,
+/// Maximum number of labels in a domain name (RFC 1035 limits names to 255 octets).
+const MAX_LABELS: usize = 128;
+
+/// Maximum length of a single label.
+pub(crate) const MAX_LABEL_LEN: usize = 63;
+
+/// Maximum length of an encoded domain name, including the root label.
+pub(crate) const MAX_NAME_LEN: usize = 255;
+
+/// A single label of a domain name.
+#[derive(Clone, Copy, Debug)]
+pub enum Label<'a> {
+    /// Label octets as they appear on the wire.
+    Wire(&'a [u8]),
+    /// Label in presentation format, possibly containing `\X` and `\DDD` escapes.
+    Text(&'a [u8]),
+}
+
+impl<'a> Label<'a> {
+    /// The octets of the label with any escapes resolved.
+    pub fn bytes(&self) -> LabelBytes<'a> {
+        match *self {
+            Self::Wire(data) => LabelBytes {
+                data,
+                escaped: false,
+            },
+            Self::Text(data) => LabelBytes {
+                data,
+                escaped: true,
+            },
+        }
+    }
+
+    /// Check the label is well formed and return its length in octets.
+    pub fn validate(&self) -> Result<usize, DomainError> {
+        let mut it = self.bytes();
+        let mut len = 0;
+        while let Some(b) = it.next_checked() {
+            b?;
+            len += 1;
+        }
+        if len == 0 {
+            Err(DomainError::EmptyLabel)
+        } else if len > MAX_LABEL_LEN {
+            Err(DomainError::LabelTooLong)
+        } else {
+            Ok(len)
+        }
+    }
+
+    fn eq_ignore_ascii_case(&self, other: &Label<'_>) -> bool {
+        self.bytes()
+            .map(|b| b.to_ascii_lowercase())
+            .eq(other.bytes().map(|b| b.to_ascii_lowercase()))
+    }
+}
+
+/// Iterator over the octets of a label.
+pub struct LabelBytes<'a> {
+    data: &'a [u8],
+    escaped: bool,
 }
 
-impl<'a> Iterator for DomainIter<'a> {
+impl<'a> LabelBytes<'a> {
+    fn next_checked(&mut self) -> Option<Result<u8, DomainError>> {
+        let (&b, rest) = self.data.split_first()?;
+        self.data = rest;
+        if !self.escaped || b != b'\\' {
+            return Some(Ok(b));
+        }
+        match self.data {
+            [d0 @ b'0'..=b'9', d1 @ b'0'..=b'9', d2 @ b'0'..=b'9', rest @ ..] => {
+                self.data = rest;
+                let v = (d0 - b'0') as u16 * 100 + (d1 - b'0') as u16 * 10 + (d2 - b'0') as u16;
+                Some(u8::try_from(v).map_err(|_| DomainError::InvalidEscape))
+            }
+            [b'0'..=b'9', ..] | [] => {
+                self.data = &[];
+                Some(Err(DomainError::InvalidEscape))
+            }
+            [c, rest @ ..] => {
+                self.data = rest;
+                Some(Ok(*c))
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for LabelBytes<'a> {
     type Item = u8;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.domain {
-            Domain::String(s) => {
-                let b = s.as_bytes();
-                if self.pos < b.len() {
-                    let pos = self.pos;
-                    self.pos += 1;
-                    Some(b[pos])
-                } else {
-                    None
+        // Malformed escapes are reported by `Label::validate`.
+        self.next_checked()?.ok()
+    }
+}
+
+/// Iterator over the labels of a domain name, from left to right.
+pub struct Labels<'a> {
+    inner: LabelsInner<'a>,
+}
+
+enum LabelsInner<'a> {
+    String {
+        s: &'a [u8],
+        done: bool,
+    },
+    Raw {
+        data: &'a [u8],
+        message: &'a [u8],
+        pos: usize,
+        jumps: usize,
+    },
+}
+
+impl<'a> Iterator for Labels<'a> {
+    type Item = Label<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            LabelsInner::String { s, done } => {
+                if *done {
+                    return None;
                 }
+                let label = match find_unescaped_dot(s) {
+                    Some(end) => {
+                        let label = &s[..end];
+                        *s = &s[end + 1..];
+                        label
+                    }
+                    None => {
+                        *done = true;
+                        s
+                    }
+                };
+                Some(Label::Text(label))
             }
-            Domain::Raw { data, message } => {
-                let mut ret = None;
-                loop {
-                    let data = if let Some(p) = self.ptr {
-                        &message[p..]
-                    } else {
-                        data
-                    };
-                    let pos = self.pos;
-                    if self.len > 0 {
-                        self.pos += 1;
-                        self.len -= 1;
-                        ret.replace(data[pos]);
-                        break;
-                    } else if data[pos] & 0xC0 != 0 {
-                        self.ptr.replace(u16::from_be_bytes([data[pos] & 0x3F, data[pos + 1]]) as usize);
-                        self.pos = 0;
-                        self.len = 0;
-                    } else if data[pos] == 0 {
-                        break;
-                    } else {
-                        self.len = data[pos] as usize;
-                        self.pos += 1;
-                        if !self.first {
-                            ret.replace(b'.');
-                            break;
-                        }
+            LabelsInner::Raw {
+                data,
+                message,
+                pos,
+                jumps,
+            } => loop {
+                let len = *data.get(*pos)?;
+                if len & 0xC0 != 0 {
+                    // Compression pointer, limit the number of jumps to avoid loops.
+                    let ptr = u16::from_be_bytes([len & 0x3F, *data.get(*pos + 1)?]) as usize;
+                    *jumps += 1;
+                    if *jumps > MAX_LABELS || ptr >= message.len() {
+                        return None;
                     }
+                    *data = message;
+                    *pos = ptr;
+                } else if len == 0 {
+                    return None;
+                } else {
+                    let start = *pos + 1;
+                    let label = data.get(start..start + len as usize)?;
+                    *pos = start + len as usize;
+                    return Some(Label::Wire(label));
                 }
-                self.first = false;
-                ret
+            },
+        }
+    }
+}
+
+/// Position of the first '.' in `s` that is not escaped.
+fn find_unescaped_dot(s: &[u8]) -> Option<usize> {
+    let mut escaped = false;
+    for (i, &b) in s.iter().enumerate() {
+        match b {
+            b'.' if !escaped => return Some(i),
+            b'\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Strip the trailing dot of an absolute name, unless the dot is escaped.
+pub(crate) fn strip_root(s: &str) -> &str {
+    match s.strip_suffix('.') {
+        Some(stripped) => {
+            let backslashes = stripped.bytes().rev().take_while(|b| *b == b'\\').count();
+            if backslashes % 2 == 0 {
+                stripped
+            } else {
+                s
             }
         }
+        None => s,
     }
 }
 
+/// Compare two labels as in RFC 4034 section 6.1: octet by octet with ASCII letters
+/// folded to lower case, where a label that is a prefix of another sorts first.
+fn cmp_label(l: &Label<'_>, r: &Label<'_>) -> Ordering {
+    l.bytes()
+        .map(|b| b.to_ascii_lowercase())
+        .cmp(r.bytes().map(|b| b.to_ascii_lowercase()))
+}
+
 impl<'a> PartialEq for Domain<'a> {
+    /// Domain names are compared ASCII case-insensitively (RFC 4343).
     fn eq(&self, other: &Self) -> bool {
-        let mut lit = self.iter();
-        let mut rit = other.iter();
+        let mut lit = self.labels();
+        let mut rit = other.labels();
         loop {
             match (lit.next(), rit.next()) {
                 (Some(l), Some(r)) => {
-                    if l != r {
+                    if !l.eq_ignore_ascii_case(&r) {
                         return false;
                     }
                 }
@@ -480,143 +809,527 @@ impl<'a> PartialEq for Domain<'a> {
     }
 }
 
-impl<'a> Domain<'a> {
-    fn iter(&'a self) -> DomainIter<'a> {
-        DomainIter {
-            domain: self,
-            pos: 0,
-            len: 0,
-            ptr: None,
-            first: true,
+impl<'a> Eq for Domain<'a> {}
+
+impl<'a> fmt::Display for Domain<'a> {
+    /// Formats the name in presentation format, escaping special characters.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut empty = true;
+        for label in self.labels() {
+            if !empty {
+                f.write_char('.')?;
+            }
+            empty = false;
+            for b in label.bytes() {
+                match b {
+                    b'.' | b'\\' => write!(f, "\\{}", b as char)?,
+                    0x21..=0x7E => f.write_char(b as char)?,
+                    _ => write!(f, "\\{:03}", b)?,
+                }
+            }
         }
+        if empty {
+            f.write_char('.')?;
+        }
+        Ok(())
     }
-    fn encode(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
-        let mut pos = 0;
-        match self {
-            Self::String(host) => {
-                let labels = host.split('.');
-                for label in labels {
-                    let label = label.as_bytes();
-                    let l = label.len().min(255);
-                    buf[pos] = l as u8;
-                    pos += 1;
-
-                    buf[pos..pos + l].copy_from_slice(&label[..l]);
-                    pos += l;
+}
+
+impl<'a> PartialOrd for Domain<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Domain<'a> {
+    /// Canonical DNS name order (RFC 4034 section 6.1): names are sorted by their
+    /// labels, starting with the rightmost label.
+    fn cmp(&self, other: &Self) -> Ordering {
+        // The labels are found by their index from the right, rather than
+        // collected, which would take kilobytes of stack
+        let l = self.labels().take(MAX_LABELS).count();
+        let r = other.labels().take(MAX_LABELS).count();
+        for (i, j) in (0..l).rev().zip((0..r).rev()) {
+            if let (Some(ll), Some(rl)) = (self.labels().nth(i), other.labels().nth(j)) {
+                match cmp_label(&ll, &rl) {
+                    Ordering::Equal => {}
+                    o => return o,
                 }
             }
-            Self::Raw { data, message: _ } => {
-                buf[pos..pos + data.len()].copy_from_slice(&data[..]);
-                pos += data.len();
+        }
+        l.cmp(&r)
+    }
+}
+
+impl<'a> Hash for Domain<'a> {
+    /// Hashes the lower case form of the name, consistent with `PartialEq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for label in self.labels() {
+            for b in label.bytes() {
+                state.write_u8(b.to_ascii_lowercase());
             }
+            state.write_u8(b'.');
         }
-        buf[pos] = 0;
-        pos += 1;
-        Ok(pos)
+        state.write_u8(0);
     }
+}
 
-    fn decode(buf: &'a [u8], message: &'a [u8]) -> Result<(usize, Domain<'a>), DnsError> {
-        let mut pos = 0;
-        loop {
-            let len = buf[pos];
-            if len & 0xC0 != 0 {
-                pos += 2;
-                break;
-            } else {
-                pos += len as usize + 1;
+impl<'a> Domain<'a> {
+    /// Iterate over the labels of this name, not including the root label.
+    ///
+    /// A name given as a string may be absolute, with a trailing dot for the root label.
+    pub fn labels(&self) -> Labels<'a> {
+        let inner = match *self {
+            Self::String(s) => {
+                let s = strip_root(s);
+                LabelsInner::String {
+                    s: s.as_bytes(),
+                    done: s.is_empty(),
+                }
             }
+            Self::Raw { data, message } => LabelsInner::Raw {
+                data,
+                message,
+                pos: 0,
+                jumps: 0,
+            },
+        };
+        Labels { inner }
+    }
 
-            if len == 0 {
-                break;
+    /// Encode the name in uncompressed wire format, validating labels and length.
+    pub(crate) fn encode(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
+        let mut enc = Encoder::new(buf, false);
+        enc.put_name(self)?;
+        Ok(enc.pos)
+    }
+
+    /// Length of the name in uncompressed wire format, including the root label.
+    pub fn encoded_len(&self) -> Result<usize, DnsError> {
+        let mut enc = Encoder::measure();
+        enc.put_name(self)?;
+        Ok(enc.pos)
+    }
+
+    /// Decode a name at the start of `buf`, which is a part of `message`.
+    ///
+    /// The whole name is validated, following compression pointers, so that
+    /// iterating over its labels later can not fail.
+    pub(crate) fn decode(
+        buf: &'a [u8],
+        message: &'a [u8],
+    ) -> Result<(usize, Domain<'a>), DnsError> {
+        let mut r = Reader::new(buf, message);
+        let mut len = None;
+        let mut total = 1;
+        loop {
+            let start = r.pos;
+            let l = r.u8()?;
+            match l & 0xC0 {
+                0xC0 => {
+                    let ptr = u16::from_be_bytes([l & 0x3F, r.u8()?]);
+                    len.get_or_insert(r.pos);
+                    // Only pointers to earlier parts of the message are allowed, which
+                    // together with the length limit rules out loops.
+                    let at = r.base + start;
+                    if ptr as usize >= at {
+                        return Err(r.error_at(start, DecodeReason::InvalidPointer(ptr)));
+                    }
+                    r = Reader::new(&message[ptr as usize..], message);
+                }
+                0x00 => {
+                    if l == 0 {
+                        len.get_or_insert(r.pos);
+                        break;
+                    }
+                    total += l as usize + 1;
+                    if total > MAX_NAME_LEN {
+                        return Err(r.error_at(start, DecodeReason::NameTooLong));
+                    }
+                    r.slice(l as usize)?;
+                }
+                _ => return Err(r.error_at(start, DecodeReason::InvalidLabel(l))),
             }
         }
+        // Always set by the loop above
+        let len = len.unwrap_or_default();
         Ok((
-            pos,
+            len,
             Domain::Raw {
-                data: &buf[..pos],
+                data: &buf[..len],
                 message,
             },
         ))
     }
 }
 
-impl<'a> DnsMessage<'a> {
-    pub(crate) fn encode(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
-        assert!(buf.len() >= 12);
-        let id = self.id.to_be_bytes();
-        buf[0] = id[0];
-        buf[1] = id[1];
-
-        // bit 0 - query
-        // bit 1-4 - opcode
-        // bit 5 - authorative (for responses, not set)
-        // bit 6 - truncation (not set)
-        // bit 7 - recursion (not set)
-        buf[2] = match self.opcode {
-            Opcode::Query => 0,
-            Opcode::IQuery => 1,
-            Opcode::Status => 2,
-        } << 3;
+/// Offset of `data` in `message`, which it is a part of.
+fn offset_in(message: &[u8], data: &[u8]) -> usize {
+    (data.as_ptr() as usize).wrapping_sub(message.as_ptr() as usize)
+}
 
-        buf[3] = 0;
+/// Reads fields from a part of a message, reporting errors with their offset in the
+/// message.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    message: &'a [u8],
+    base: usize,
+    pos: usize,
+}
 
-        buf[4..6].copy_from_slice(&(self.questions.count() as u16).to_be_bytes()); // QDCOUNT
-        buf[6..8].copy_from_slice(&(self.answers.count() as u16).to_be_bytes()); // ANCOUNT
-        buf[8] = 0; // NSCOUNT
-        buf[9] = 0; // NSCOUNT
-        buf[10] = 0; // ARCOUNT
-        buf[11] = 0; // ARCOUNT
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8], message: &'a [u8]) -> Self {
+        Self {
+            data,
+            message,
+            base: offset_in(message, data),
+            pos: 0,
+        }
+    }
 
-        let mut pos = 12;
-        pos += self.questions.encode(&mut buf[pos..])?;
+    pub(crate) fn error_at(&self, pos: usize, reason: DecodeReason) -> DnsError {
+        DnsError::Decode(DecodeError {
+            offset: self.base + pos,
+            reason,
+        })
+    }
 
-        pos += self.answers.encode(&mut buf[pos..])?;
+    pub(crate) fn error(&self, reason: DecodeReason) -> DnsError {
+        self.error_at(self.pos, reason)
+    }
 
-        Ok(pos)
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.pos
     }
 
-    pub(crate) fn decode(buf: &'a [u8]) -> Result<DnsMessage<'a>, DnsError> {
-        assert!(buf.len() >= 12);
-        let id = u16::from_be_bytes([buf[0], buf[1]]);
+    pub(crate) fn slice(&mut self, len: usize) -> Result<&'a [u8], DnsError> {
+        let data = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| self.error(DecodeReason::UnexpectedEnd))?;
+        self.pos += len;
+        Ok(data)
+    }
 
-        let opcode = match (buf[2] >> 3) & 0xF {
-            0 => Ok(Opcode::Query),
-            1 => Ok(Opcode::IQuery),
-            2 => Ok(Opcode::Status),
-            _ => Err(DnsError::Decode),
-        }?;
+    pub(crate) fn u8(&mut self) -> Result<u8, DnsError> {
+        Ok(self.slice(1)?[0])
+    }
 
-        // TODO: Other bits in buf[2]
+    pub(crate) fn u16(&mut self) -> Result<u16, DnsError> {
+        let b = self.slice(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
 
-        let rcode = buf[3] & 0xF;
+    pub(crate) fn u32(&mut self) -> Result<u32, DnsError> {
+        let b = self.slice(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub(crate) fn name(&mut self) -> Result<Domain<'a>, DnsError> {
+        let (len, name) = Domain::decode(&self.data[self.pos..], self.message)?;
+        self.pos += len;
+        Ok(name)
+    }
+}
+
+/// Maximum number of names remembered for compression while encoding.
+const MAX_COMPRESSED_NAMES: usize = 32;
+
+/// Writes a message into a buffer, optionally compressing names (RFC 1035 section 4.1.4).
+///
+/// All writes are bounds checked, failing with [`DnsError::Encode`] when the buffer is
+/// too small. An encoder created with [`Encoder::measure`] only counts octets.
+pub(crate) struct Encoder<'b> {
+    buf: &'b mut [u8],
+    pos: usize,
+    measure: bool,
+    compress: bool,
+    names: Vec<u16, MAX_COMPRESSED_NAMES>,
+}
+
+impl<'b> Encoder<'b> {
+    pub(crate) fn new(buf: &'b mut [u8], compress: bool) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            measure: false,
+            compress,
+            names: Vec::new(),
+        }
+    }
+
+    /// An encoder that computes the uncompressed length without writing anything.
+    pub(crate) fn measure() -> Encoder<'static> {
+        Encoder {
+            buf: &mut [],
+            pos: 0,
+            measure: true,
+            compress: false,
+            names: Vec::new(),
+        }
+    }
+
+    fn put_u8(&mut self, value: u8) -> Result<(), DnsError> {
+        self.put_slice(&[value])
+    }
+
+    fn put_u16(&mut self, value: u16) -> Result<(), DnsError> {
+        self.put_slice(&value.to_be_bytes())
+    }
 
-        match rcode {
-            1 => return Err(DnsError::FormatError),
-            2 => return Err(DnsError::ServerFailure),
-            3 => return Err(DnsError::NameError),
-            4 => return Err(DnsError::NotImplemented),
-            5 => return Err(DnsError::Refused),
-            _ => {}
+    fn put_u32(&mut self, value: u32) -> Result<(), DnsError> {
+        self.put_slice(&value.to_be_bytes())
+    }
+
+    fn put_slice(&mut self, data: &[u8]) -> Result<(), DnsError> {
+        if !self.measure {
+            self.buf
+                .get_mut(self.pos..self.pos + data.len())
+                .ok_or(DnsError::Encode)?
+                .copy_from_slice(data);
+        }
+        self.pos += data.len();
+        Ok(())
+    }
+
+    fn put_name(&mut self, name: &Domain<'_>) -> Result<(), DnsError> {
+        let mut labels: Vec<Label<'_>, MAX_LABELS> = Vec::new();
+        let mut len = 1;
+        for label in name.labels() {
+            len += label.validate().map_err(DnsError::InvalidDomain)? + 1;
+            if len > MAX_NAME_LEN || labels.push(label).is_err() {
+                return Err(DnsError::InvalidDomain(DomainError::NameTooLong));
+            }
+        }
+
+        for i in 0..labels.len() {
+            if self.compress {
+                if let Some(offset) = self.find_name(&labels[i..]) {
+                    self.put_u16(0xC000 | offset)?;
+                    return Ok(());
+                }
+                // Pointers can only refer to the first 16k of a message.
+                if self.pos <= 0x3FFF {
+                    self.names.push(self.pos as u16).ok();
+                }
+            }
+            let label = &labels[i];
+            self.put_u8(label.bytes().count() as u8)?;
+            for b in label.bytes() {
+                self.put_u8(b)?;
+            }
+        }
+        self.put_u8(0)?;
+        Ok(())
+    }
+
+    /// Find a previously written name equal to `labels`.
+    fn find_name(&self, labels: &[Label<'_>]) -> Option<u16> {
+        let message = &self.buf[..self.pos];
+        self.names.iter().copied().find(|&offset| {
+            let name = Domain::Raw {
+                data: &message[offset as usize..],
+                message,
+            };
+            let mut it = name.labels();
+            labels
+                .iter()
+                .all(|l| it.next().map_or(false, |o| o.eq_ignore_ascii_case(l)))
+                && it.next().is_none()
+        })
+    }
+}
+
+impl<'a> DnsMessage<'a> {
+    /// Encode the message into `buf`, returning the encoded length.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
+        self.encode_with(Encoder::new(buf, false))
+    }
+
+    /// Encode the message into `buf` like [`DnsMessage::encode`], replacing repeated
+    /// names with pointers to their first occurrence.
+    pub fn encode_compressed(&self, buf: &mut [u8]) -> Result<usize, DnsError> {
+        self.encode_with(Encoder::new(buf, true))
+    }
+
+    /// Length of the message when encoded with [`DnsMessage::encode`], which is also an
+    /// upper bound for [`DnsMessage::encode_compressed`].
+    pub fn encoded_len(&self) -> Result<usize, DnsError> {
+        self.encode_with(Encoder::measure())
+    }
+
+    fn encode_with(&self, mut enc: Encoder<'_>) -> Result<usize, DnsError> {
+        enc.put_u16(self.id)?;
+
+        let opcode = match self.opcode {
+            Opcode::Query => 0,
+            Opcode::IQuery => 1,
+            Opcode::Status => 2,
+        };
+        let flags = &self.flags;
+        enc.put_u8(
+            (flags.response as u8) << 7
+                | opcode << 3
+                | (flags.authoritative as u8) << 2
+                | (flags.truncated as u8) << 1
+                | flags.recursion_desired as u8,
+        )?;
+        enc.put_u8(
+            (flags.recursion_available as u8) << 7
+                | (flags.authentic_data as u8) << 5
+                | (flags.checking_disabled as u8) << 4
+                | self.rcode.header(),
+        )?;
+
+        let count = |n: usize| u16::try_from(n).map_err(|_| DnsError::Encode);
+        enc.put_u16(count(self.questions.count())?)?; // QDCOUNT
+        enc.put_u16(count(self.answers.count())?)?; // ANCOUNT
+        enc.put_u16(count(self.authorities.count())?)?; // NSCOUNT
+        let additionals = self.additionals.count() + self.edns.is_some() as usize;
+        enc.put_u16(count(additionals)?)?; // ARCOUNT
+
+        self.questions.encode(&mut enc)?;
+        self.answers.encode(&mut enc)?;
+        self.authorities.encode(&mut enc)?;
+        self.additionals.encode(&mut enc)?;
+
+        if let Some(edns) = &self.edns {
+            let ttl = (self.rcode.extended() as u32) << 24
+                | (edns.version as u32) << 16
+                | (edns.dnssec_ok as u32) << 15;
+            Answer {
+                domain: Domain::String("."),
+                r#type: QType::OPT,
+                class: QClass::Unknown(edns.udp_payload_size),
+                ttl,
+                rdata: &[],
+            }
+            .encode(&mut enc)?;
         }
 
-        let questions = u16::from_be_bytes([buf[4], buf[5]]);
-        let answers = u16::from_be_bytes([buf[6], buf[7]]);
+        Ok(enc.pos)
+    }
 
-        // Skip NSCOUNT, ARCOUNT
-        let mut pos = 12;
+    /// Decode a message from `buf`.
+    ///
+    /// A response code other than [`Rcode::NOERROR`] is not an error at this
+    /// level, it is available in [`DnsMessage::rcode`].
+    pub fn decode(buf: &'a [u8]) -> Result<DnsMessage<'a>, DnsError> {
+        Ok(Layout::decode(buf)?.message(buf))
+    }
+}
 
-        let (p, questions) = Questions::decode(questions as usize, &buf[pos..], buf)?;
-        pos += p;
+/// A decoded message that does not borrow the buffer it was decoded from, so
+/// that the buffer can be reused or copied while it is kept.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Layout {
+    /// Length of the message.
+    pub(crate) len: usize,
+    pub(crate) id: u16,
+    pub(crate) opcode: Opcode,
+    pub(crate) flags: Flags,
+    pub(crate) rcode: Rcode,
+    pub(crate) edns: Option<Edns>,
+    /// The number of entries, start and end of each section.
+    sections: [(usize, usize, usize); 4],
+}
+
+impl Layout {
+    /// Decode a message from `buf`, checking all of its sections.
+    pub(crate) fn decode(buf: &[u8]) -> Result<Layout, DnsError> {
+        let mut r = Reader::new(buf, buf);
+        let id = r.u16()?;
+
+        let b2 = r.u8()?;
+        let opcode = match (b2 >> 3) & 0xF {
+            0 => Opcode::Query,
+            1 => Opcode::IQuery,
+            2 => Opcode::Status,
+            op => return Err(r.error_at(2, DecodeReason::UnknownOpcode(op))),
+        };
+        let b3 = r.u8()?;
+        let flags = Flags {
+            response: b2 & 0x80 != 0,
+            authoritative: b2 & 0x04 != 0,
+            truncated: b2 & 0x02 != 0,
+            recursion_desired: b2 & 0x01 != 0,
+            recursion_available: b3 & 0x80 != 0,
+            authentic_data: b3 & 0x20 != 0,
+            checking_disabled: b3 & 0x10 != 0,
+        };
+
+        let mut sections = [(0, 0, 0); 4];
+        for (count, _, _) in sections.iter_mut() {
+            *count = r.u16()? as usize;
+        }
+        let mut pos = r.pos;
+        for (i, (count, start, end)) in sections.iter_mut().enumerate() {
+            let p = match i {
+                0 => Questions::decode(*count, &buf[pos..], buf)?.0,
+                _ => Answers::decode(*count, &buf[pos..], buf)?.0,
+            };
+            *start = pos;
+            pos += p;
+            *end = pos;
+        }
 
-        let (_p, answers) = Answers::decode(answers as usize, &buf[pos..], buf)?;
+        let mut edns = None;
+        let mut extended = 0;
+        let (count, start, end) = sections[3];
+        let additionals = Answers::Raw {
+            message: buf,
+            count,
+            data: &buf[start..end],
+        };
+        for answer in additionals.iter() {
+            let answer = answer?;
+            if answer.r#type == QType::OPT {
+                edns.replace(Edns {
+                    udp_payload_size: answer.class.into(),
+                    version: (answer.ttl >> 16) as u8,
+                    dnssec_ok: answer.ttl & 0x8000 != 0,
+                });
+                extended = (answer.ttl >> 24) as u8;
+            }
+        }
 
-        Ok(DnsMessage {
+        Ok(Layout {
+            len: buf.len(),
             id,
             opcode,
-            questions,
-            answers,
+            flags,
+            rcode: Rcode::new(b3 & 0xF, extended),
+            edns,
+            sections,
         })
     }
+
+    /// The message in `buf`, which is the buffer it was decoded from or a copy
+    /// of it, without decoding it again.
+    pub(crate) fn message<'a>(&self, buf: &'a [u8]) -> DnsMessage<'a> {
+        let message = &buf[..self.len];
+        let answers = |(count, start, end): (usize, usize, usize)| Answers::Raw {
+            message,
+            count,
+            data: &message[start..end],
+        };
+        let (count, start, end) = self.sections[0];
+        DnsMessage {
+            id: self.id,
+            opcode: self.opcode,
+            flags: self.flags,
+            rcode: self.rcode,
+            questions: Questions::Raw {
+                message,
+                count,
+                data: &message[start..end],
+            },
+            answers: answers(self.sections[1]),
+            authorities: answers(self.sections[2]),
+            additionals: answers(self.sections[3]),
+            edns: self.edns,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -631,12 +1344,17 @@ mod tests {
         let len = DnsMessage {
             id: 2,
             opcode: Opcode::Query,
+            flags: Flags::default(),
+            rcode: Rcode::NOERROR,
             questions: Questions::Slice(&[Question {
                 qname: Domain::String("google.com"),
                 qtype: QType::A,
                 qclass: QClass::IN,
             }]),
             answers: Answers::Slice(&[]),
+            authorities: Answers::Slice(&[]),
+            additionals: Answers::Slice(&[]),
+            edns: None,
         }
         .encode(&mut buf[..])
         .unwrap();
@@ -647,4 +1365,340 @@ mod tests {
         let question = m.questions.get(0).unwrap().unwrap();
         assert_eq!(Domain::String("google.com"), question.qname);
     }
+
+    const A_RECORD: Answer<'static> = Answer {
+        domain: Domain::String("example.com"),
+        r#type: QType::A,
+        class: QClass::IN,
+        ttl: 300,
+        rdata: &[192, 0, 2, 1],
+    };
+
+    fn encode_response(buf: &mut [u8], answers: &[Answer<'_>]) -> usize {
+        DnsMessage {
+            id: 1,
+            opcode: Opcode::Query,
+            flags: Flags::default(),
+            rcode: Rcode::NOERROR,
+            questions: Questions::Slice(&[Question {
+                qname: Domain::String("example.com"),
+                qtype: QType::A,
+                qclass: QClass::IN,
+            }]),
+            answers: Answers::Slice(answers),
+            authorities: Answers::Slice(&[]),
+            additionals: Answers::Slice(&[]),
+            edns: None,
+        }
+        .encode(buf)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_iter_answers() {
+        let mut buf = [0; 1024];
+        let mut answers = [A_RECORD; 3];
+        answers[1].rdata = &[192, 0, 2, 2];
+        answers[2].rdata = &[192, 0, 2, 3];
+        let len = encode_response(&mut buf, &answers);
+
+        let m = DnsMessage::decode(&buf[..len]).unwrap();
+        assert_eq!(1, m.questions.iter().count());
+
+        let mut it = m.answers.iter();
+        for expected in answers.iter() {
+            let answer = it.next().unwrap().unwrap();
+            assert_eq!(Domain::String("example.com"), answer.domain);
+            assert_eq!(expected.rdata, answer.rdata);
+        }
+        assert!(it.next().is_none());
+        assert_eq!(answers[2].rdata, m.answers.get(2).unwrap().unwrap().rdata);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut buf = [0; 512];
+        let len = encode_response(&mut buf, &[A_RECORD]);
+        let error = |data: &[u8]| match DnsMessage::decode(data) {
+            Err(DnsError::Decode(e)) => (e.offset, e.reason),
+            r => panic!("unexpected result {:?}", r),
+        };
+
+        assert_eq!((10, DecodeReason::UnexpectedEnd), error(&buf[..11]));
+        // Reported at the start of the 4 byte rdata that does not fit
+        assert_eq!(
+            (len - 4, DecodeReason::UnexpectedEnd),
+            error(&buf[..len - 1])
+        );
+
+        // Forward pointer in the answer name, which starts at offset 29
+        let mut data = buf;
+        data[29..31].copy_from_slice(&[0xC0, 29]);
+        assert_eq!((29, DecodeReason::InvalidPointer(29)), error(&data[..len]));
+
+        // Pointer loop, the question name points to itself
+        let mut data = buf;
+        data[12..14].copy_from_slice(&[0xC0, 12]);
+        assert_eq!((12, DecodeReason::InvalidPointer(12)), error(&data[..len]));
+
+        // Reserved label type
+        let mut data = buf;
+        data[20] = 0x43;
+        assert_eq!((20, DecodeReason::InvalidLabel(0x43)), error(&data[..len]));
+    }
+
+    #[test]
+    fn test_edns_rcode() {
+        let message = DnsMessage {
+            id: 1,
+            opcode: Opcode::Query,
+            flags: Flags {
+                response: true,
+                truncated: true,
+                authentic_data: true,
+                ..Default::default()
+            },
+            rcode: Rcode::BADVERS,
+            questions: Questions::Slice(&[]),
+            answers: Answers::Slice(&[A_RECORD]),
+            authorities: Answers::Slice(&[]),
+            additionals: Answers::Slice(&[A_RECORD]),
+            edns: Some(Edns {
+                udp_payload_size: 1232,
+                version: 0,
+                dnssec_ok: true,
+            }),
+        };
+        let mut buf = [0; 512];
+        let len = message.encode(&mut buf).unwrap();
+
+        let m = DnsMessage::decode(&buf[..len]).unwrap();
+        assert_eq!(message.flags, m.flags);
+        assert_eq!(Rcode::BADVERS, m.rcode);
+        assert_eq!(0, m.rcode.header());
+        assert_eq!(1, m.rcode.extended());
+        assert_eq!(message.edns, m.edns);
+        assert_eq!(1, m.answers.count());
+        assert_eq!(2, m.additionals.count());
+    }
+
+    #[test]
+    fn test_encode_compressed() {
+        let mut answers = [A_RECORD; 3];
+        answers[1].domain = Domain::String("www.EXAMPLE.com");
+        answers[2].domain = Domain::String("www.example.org");
+        let message = DnsMessage {
+            id: 1,
+            opcode: Opcode::Query,
+            flags: Flags::default(),
+            rcode: Rcode::NOERROR,
+            questions: Questions::Slice(&[Question {
+                qname: Domain::String("example.com"),
+                qtype: QType::A,
+                qclass: QClass::IN,
+            }]),
+            answers: Answers::Slice(&answers),
+            authorities: Answers::Slice(&[]),
+            additionals: Answers::Slice(&[]),
+            edns: None,
+        };
+
+        let mut plain = [0; 512];
+        let plain_len = message.encode(&mut plain).unwrap();
+        let mut buf = [0; 512];
+        let len = message.encode_compressed(&mut buf).unwrap();
+        // example.com becomes a pointer, www.example.com a label and a pointer
+        assert_eq!(plain_len - 11 - 11, len);
+
+        let m = DnsMessage::decode(&buf[..len]).unwrap();
+        assert_eq!(
+            Domain::String("example.com"),
+            m.questions.get(0).unwrap().unwrap().qname
+        );
+        for (expected, answer) in answers.iter().zip(m.answers.iter()) {
+            let answer = answer.unwrap();
+            assert_eq!(expected.domain, answer.domain);
+            assert_eq!(expected.rdata, answer.rdata);
+        }
+
+        // Re-encoding a decoded message expands the original pointers.
+        let mut reencoded = [0; 512];
+        assert_eq!(plain_len, m.encode(&mut reencoded).unwrap());
+    }
+
+    #[test]
+    fn test_encode_bounds() {
+        let long = [b'a'; 63];
+        let label = core::str::from_utf8(&long).unwrap();
+        let mut name = std::string::String::new();
+        for _ in 0..3 {
+            name.push_str(label);
+            name.push('.');
+        }
+        name.push_str(&label[..61]);
+        let answers = [Answer {
+            domain: Domain::String(&name),
+            ..A_RECORD
+        }; 2];
+        let questions = [Question {
+            qname: Domain::String(&name),
+            qtype: QType::A,
+            qclass: QClass::IN,
+        }];
+        let message = DnsMessage {
+            id: 1,
+            opcode: Opcode::Query,
+            flags: Flags::default(),
+            rcode: Rcode::NOERROR,
+            questions: Questions::Slice(&questions),
+            answers: Answers::Slice(&answers),
+            authorities: Answers::Slice(&[]),
+            additionals: Answers::Slice(&[]),
+            edns: None,
+        };
+
+        let len = message.encoded_len().unwrap();
+        assert_eq!(12 + 255 + 4 + 2 * (255 + 14), len);
+
+        let mut buf = [0; 1024];
+        for size in 0..len {
+            assert!(matches!(
+                message.encode(&mut buf[..size]),
+                Err(DnsError::Encode)
+            ));
+        }
+        assert_eq!(len, message.encode(&mut buf[..len]).unwrap());
+        assert!(message.encode_compressed(&mut buf[..512]).unwrap() < 512);
+        assert_eq!(255, Domain::String(&name).encoded_len().unwrap());
+    }
+
+    #[test]
+    fn test_encode_domain() {
+        let mut buf = [0; 512];
+        let len = Domain::String("example.com.").encode(&mut buf).unwrap();
+        assert_eq!(b"\x07example\x03com\x00", &buf[..len]);
+
+        let len = Domain::String("a\\.b.c").encode(&mut buf).unwrap();
+        assert_eq!(b"\x03a.b\x01c\x00", &buf[..len]);
+
+        let len = Domain::String("\\065\\\\.").encode(&mut buf).unwrap();
+        assert_eq!(b"\x02A\\\x00", &buf[..len]);
+
+        let len = Domain::String(".").encode(&mut buf).unwrap();
+        assert_eq!(b"\x00", &buf[..len]);
+
+        fn err(name: &str) -> DomainError {
+            match Domain::String(name).encode(&mut [0; 512]) {
+                Err(DnsError::InvalidDomain(e)) => e,
+                r => panic!("unexpected result for {}: {:?}", name, r),
+            }
+        }
+        assert_eq!(DomainError::EmptyLabel, err("example..com"));
+        assert_eq!(DomainError::EmptyLabel, err(".example.com"));
+        assert_eq!(DomainError::EmptyLabel, err("example.com.."));
+        assert_eq!(DomainError::InvalidEscape, err("a\\256.com"));
+        assert_eq!(DomainError::InvalidEscape, err("a\\12.com"));
+        assert_eq!(DomainError::InvalidEscape, err("com\\"));
+
+        let long = [b'a'; 64];
+        let long = core::str::from_utf8(&long).unwrap();
+        assert_eq!(DomainError::LabelTooLong, err(long));
+
+        let mut name = std::string::String::new();
+        for _ in 0..4 {
+            name.push_str(&long[..63]);
+            name.push('.');
+        }
+        assert_eq!(DomainError::NameTooLong, err(&name));
+        name.truncate(name.len() - 3);
+        assert_eq!(253, name.len());
+        assert_eq!(255, Domain::String(&name).encode(&mut buf).unwrap());
+    }
+
+    #[test]
+    fn test_domain_case_insensitive() {
+        let mut buf = [0; 1024];
+        let len = encode_response(&mut buf, &[A_RECORD]);
+        let m = DnsMessage::decode(&buf[..len]).unwrap();
+        let answer = m.answers.get(0).unwrap().unwrap();
+
+        assert_eq!(Domain::String("Example.COM"), answer.domain);
+        assert_eq!(
+            Domain::String("example.com."),
+            Domain::String("EXAMPLE.com")
+        );
+        assert_ne!(Domain::String("example.co"), answer.domain);
+
+        fn hash(d: &Domain<'_>) -> u64 {
+            use std::collections::hash_map::DefaultHasher;
+            let mut h = DefaultHasher::new();
+            d.hash(&mut h);
+            h.finish()
+        }
+        assert_eq!(hash(&Domain::String("Example.COM")), hash(&answer.domain));
+    }
+
+    #[test]
+    fn test_domain_canonical_order() {
+        // Example from RFC 4034 section 6.1, \001 and \200 are raw label octets.
+        let z001 = [
+            1, 1, 1, b'z', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0,
+        ];
+        let z200 = [
+            1, 200, 1, b'z', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0,
+        ];
+        let ordered = [
+            Domain::String("example"),
+            Domain::String("a.example"),
+            Domain::String("yljkjljk.a.example"),
+            Domain::String("Z.a.example"),
+            Domain::String("zABC.a.EXAMPLE"),
+            Domain::String("z.example"),
+            Domain::Raw {
+                data: &z001,
+                message: &z001,
+            },
+            Domain::String("*.z.example"),
+            Domain::Raw {
+                data: &z200,
+                message: &z200,
+            },
+        ];
+        for w in ordered.windows(2) {
+            assert_eq!(Ordering::Less, w[0].cmp(&w[1]));
+            assert_eq!(Ordering::Greater, w[1].cmp(&w[0]));
+        }
+    }
+
+    extern crate test;
+
+    const BENCH_RECORDS: usize = 200;
+
+    fn bench_response(buf: &mut [u8]) -> usize {
+        encode_response(buf, &[A_RECORD; BENCH_RECORDS])
+    }
+
+    #[bench]
+    fn bench_answers_get(b: &mut test::Bencher) {
+        let mut buf = [0; 8192];
+        let len = bench_response(&mut buf);
+        let m = DnsMessage::decode(&buf[..len]).unwrap();
+        b.iter(|| {
+            for i in 0..m.answers.count() {
+                test::black_box(m.answers.get(i).unwrap().unwrap());
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_answers_iter(b: &mut test::Bencher) {
+        let mut buf = [0; 8192];
+        let len = bench_response(&mut buf);
+        let m = DnsMessage::decode(&buf[..len]).unwrap();
+        b.iter(|| {
+            for answer in m.answers.iter() {
+                test::black_box(answer.unwrap());
+            }
+        });
+    }
 }
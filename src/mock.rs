@@ -0,0 +1,463 @@
+//! A UDP stack and clock for testing the client without a network.
+
+extern crate std;
+
+use core::cell::{Cell, RefCell};
+use embedded_io::ErrorKind;
+use embedded_nal_async::{
+    ConnectedUdp, IpAddr, Ipv4Addr, SocketAddr, TcpConnect, UdpStack, UnconnectedUdp,
+};
+use std::boxed::Box;
+use std::rc::Rc;
+use std::vec::Vec;
+
+use crate::{
+    Answer, Answers, Clock, DnsMessage, Domain, Flags, QClass, QType, Question, Questions, Rcode,
+};
+
+/// How a mock server replies to a query.
+pub(crate) enum Reply {
+    /// Answer with records of the given type and data, for the name asked for.
+    Records(Vec<(QType, Vec<u8>)>),
+    /// Answer with records of any name, and add records to the additional
+    /// section.
+    Sections(Vec<Record>, Vec<Record>),
+    /// Reply with an error response code.
+    Rcode(Rcode),
+    /// Never reply.
+    Silent,
+    /// Fail sending the query.
+    Fail,
+    /// Reply later than the server usually does, by milliseconds.
+    Delayed(u64, Box<Reply>),
+    /// Reply with the AD bit set, as validated by the server.
+    Authentic(Box<Reply>),
+    /// Reply with the question replaced by one for another name and type, as
+    /// a forged response with a guessed id would.
+    Forged(&'static str, QType, Box<Reply>),
+    /// Reply with the TC bit set and no records over UDP, and as usual over TCP.
+    Truncated(Box<Reply>),
+}
+
+/// The name, type and data of a record.
+pub(crate) type Record = (&'static str, QType, Vec<u8>);
+
+type Handler = Box<dyn Fn(&Question<'_>) -> Reply>;
+
+/// A mock server, replying to queries with a handler and recording them.
+pub(crate) struct Server {
+    pub addr: SocketAddr,
+    handler: Handler,
+    /// Delay before the reply, in milliseconds of the [`MockClock`].
+    delay_ms: u64,
+    pub queries: Cell<usize>,
+    pub tcp_queries: Cell<usize>,
+}
+
+pub(crate) fn server(n: u8) -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, n)), 53)
+}
+
+impl Server {
+    pub(crate) fn new(n: u8, handler: impl Fn(&Question<'_>) -> Reply + 'static) -> Self {
+        Self {
+            addr: server(n),
+            handler: Box::new(handler),
+            delay_ms: 0,
+            queries: Cell::new(0),
+            tcp_queries: Cell::new(0),
+        }
+    }
+
+    pub(crate) fn delay(mut self, delay_ms: u64) -> Self {
+        self.delay_ms = delay_ms;
+        self
+    }
+}
+
+/// Reply to A queries with a single address.
+pub(crate) fn a(ip: [u8; 4]) -> impl Fn(&Question<'_>) -> Reply {
+    move |q| match q.qtype {
+        QType::A => Reply::Records(std::vec![(QType::A, ip.to_vec())]),
+        _ => Reply::Records(Vec::new()),
+    }
+}
+
+pub(crate) struct MockStack {
+    pub servers: Vec<Rc<Server>>,
+    pub clock: MockClock,
+}
+
+impl MockStack {
+    pub(crate) fn new(servers: Vec<Server>) -> Self {
+        Self {
+            servers: servers.into_iter().map(Rc::new).collect(),
+            clock: MockClock::default(),
+        }
+    }
+
+    pub(crate) fn queries(&self, n: usize) -> usize {
+        self.servers[n].queries.get()
+    }
+}
+
+pub(crate) struct MockConnection {
+    server: Rc<Server>,
+    clock: MockClock,
+    response: Option<(u64, Vec<u8>)>,
+}
+
+impl UdpStack for &MockStack {
+    type Error = ErrorKind;
+    type Connected = MockConnection;
+    type UniquelyBound = MockConnection;
+    type MultiplyBound = MockConnection;
+
+    async fn connect_from(
+        &self,
+        local: SocketAddr,
+        remote: SocketAddr,
+    ) -> Result<(SocketAddr, Self::Connected), Self::Error> {
+        let server = self
+            .servers
+            .iter()
+            .find(|s| s.addr == remote)
+            .ok_or(ErrorKind::Other)?;
+        Ok((
+            local,
+            MockConnection {
+                server: server.clone(),
+                clock: self.clock.clone(),
+                response: None,
+            },
+        ))
+    }
+
+    async fn bind_single(
+        &self,
+        _local: SocketAddr,
+    ) -> Result<(SocketAddr, Self::UniquelyBound), Self::Error> {
+        Err(ErrorKind::Other)
+    }
+
+    async fn bind_multiple(&self, _local: SocketAddr) -> Result<Self::MultiplyBound, Self::Error> {
+        Err(ErrorKind::Other)
+    }
+}
+
+impl ConnectedUdp for MockConnection {
+    type Error = ErrorKind;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        let server = &self.server;
+        server.queries.set(server.queries.get() + 1);
+        self.response = respond(server, &self.clock, data, false)?;
+        Ok(())
+    }
+
+    async fn receive_into(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        match self.response.take() {
+            Some((at, response)) => {
+                self.clock.until(at).await;
+                buffer[..response.len()].copy_from_slice(&response);
+                Ok(response.len())
+            }
+            None => core::future::pending().await,
+        }
+    }
+}
+
+impl UnconnectedUdp for MockConnection {
+    type Error = ErrorKind;
+
+    async fn send(
+        &mut self,
+        _local: SocketAddr,
+        _remote: SocketAddr,
+        _data: &[u8],
+    ) -> Result<(), Self::Error> {
+        Err(ErrorKind::Other)
+    }
+
+    async fn receive_into(
+        &mut self,
+        _buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, SocketAddr), Self::Error> {
+        Err(ErrorKind::Other)
+    }
+}
+
+/// Reply to the query in `data` as `server` would, over UDP or TCP, returning
+/// when to deliver the response, if at all.
+fn respond(
+    server: &Server,
+    clock: &MockClock,
+    data: &[u8],
+    tcp: bool,
+) -> Result<Option<(u64, Vec<u8>)>, ErrorKind> {
+    let query = DnsMessage::decode(data).unwrap();
+    let question = query.questions.get(0).unwrap().unwrap();
+    let mut delay_ms = server.delay_ms;
+    let mut reply = (server.handler)(&question);
+    let mut authentic_data = false;
+    let mut truncated = false;
+    let mut asked = question;
+    let reply = loop {
+        match reply {
+            Reply::Delayed(ms, r) => {
+                delay_ms += ms;
+                reply = *r;
+            }
+            Reply::Authentic(r) => {
+                authentic_data = true;
+                reply = *r;
+            }
+            Reply::Forged(qname, qtype, r) => {
+                asked.qname = Domain::String(qname);
+                asked.qtype = qtype;
+                reply = *r;
+            }
+            Reply::Truncated(_) if !tcp => {
+                truncated = true;
+                break Reply::Records(Vec::new());
+            }
+            Reply::Truncated(r) => reply = *r,
+            reply => break reply,
+        }
+    };
+    let (answers, additionals, rcode) = match &reply {
+        Reply::Fail => return Err(ErrorKind::Other),
+        Reply::Silent => return Ok(None),
+        Reply::Records(records) => {
+            let answers = records
+                .iter()
+                .map(|(r#type, rdata)| record(question.qname, *r#type, rdata))
+                .collect();
+            (answers, Vec::new(), Rcode::NOERROR)
+        }
+        Reply::Sections(answers, additionals) => {
+            (section(answers), section(additionals), Rcode::NOERROR)
+        }
+        Reply::Rcode(rcode) => (Vec::new(), Vec::new(), *rcode),
+        Reply::Delayed(..) | Reply::Authentic(_) | Reply::Forged(..) | Reply::Truncated(_) => {
+            unreachable!()
+        }
+    };
+    let flags = Flags {
+        response: true,
+        truncated,
+        recursion_desired: true,
+        recursion_available: true,
+        authentic_data,
+        ..Default::default()
+    };
+    let response = response(&query, asked, flags, &answers, &additionals, rcode);
+    Ok(Some((clock.now_ms() + delay_ms, response)))
+}
+
+/// A connection to a mock server over TCP, with queries and responses
+/// prefixed with their length.
+pub(crate) struct MockTcpDns {
+    server: Rc<Server>,
+    clock: MockClock,
+    received: Vec<u8>,
+    response: Option<(u64, Vec<u8>)>,
+}
+
+impl TcpConnect for &MockStack {
+    type Error = ErrorKind;
+    type Connection<'a> = MockTcpDns where Self: 'a;
+
+    async fn connect<'a>(&'a self, remote: SocketAddr) -> Result<MockTcpDns, ErrorKind>
+    where
+        Self: 'a,
+    {
+        let server = self
+            .servers
+            .iter()
+            .find(|s| s.addr == remote)
+            .ok_or(ErrorKind::Other)?;
+        Ok(MockTcpDns {
+            server: server.clone(),
+            clock: self.clock.clone(),
+            received: Vec::new(),
+            response: None,
+        })
+    }
+}
+
+impl embedded_io::Io for MockTcpDns {
+    type Error = ErrorKind;
+}
+
+impl embedded_io::asynch::Write for MockTcpDns {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.received.extend_from_slice(buf);
+        if let [a, b, query @ ..] = &self.received[..] {
+            if query.len() == u16::from_be_bytes([*a, *b]) as usize {
+                let server = &self.server;
+                server.tcp_queries.set(server.tcp_queries.get() + 1);
+                self.response = respond(server, &self.clock, query, true)?.map(|(at, r)| {
+                    let mut framed = (r.len() as u16).to_be_bytes().to_vec();
+                    framed.extend_from_slice(&r);
+                    (at, framed)
+                });
+            }
+        }
+        Ok(buf.len())
+    }
+}
+
+impl embedded_io::asynch::Read for MockTcpDns {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match &mut self.response {
+            Some((at, response)) => {
+                self.clock.until(*at).await;
+                let len = buf.len().min(response.len());
+                buf[..len].copy_from_slice(&response[..len]);
+                response.drain(..len);
+                Ok(len)
+            }
+            None => core::future::pending().await,
+        }
+    }
+}
+
+/// A TCP stack accepting connections to some addresses, refusing them to
+/// others, and never answering the rest.
+#[derive(Default)]
+pub(crate) struct MockTcp {
+    pub accept: Vec<SocketAddr>,
+    pub refuse: Vec<SocketAddr>,
+    pub attempts: RefCell<Vec<SocketAddr>>,
+}
+
+pub(crate) struct MockTcpConnection(pub SocketAddr);
+
+impl embedded_io::Io for MockTcpConnection {
+    type Error = ErrorKind;
+}
+
+impl embedded_io::asynch::Read for MockTcpConnection {
+    async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(0)
+    }
+}
+
+impl embedded_io::asynch::Write for MockTcpConnection {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Ok(buf.len())
+    }
+}
+
+impl TcpConnect for &MockTcp {
+    type Error = ErrorKind;
+    type Connection<'a> = MockTcpConnection where Self: 'a;
+
+    async fn connect<'a>(&'a self, remote: SocketAddr) -> Result<Self::Connection<'a>, Self::Error>
+    where
+        Self: 'a,
+    {
+        self.attempts.borrow_mut().push(remote);
+        if self.accept.contains(&remote) {
+            Ok(MockTcpConnection(remote))
+        } else if self.refuse.contains(&remote) {
+            Err(ErrorKind::Other)
+        } else {
+            core::future::pending().await
+        }
+    }
+}
+
+fn record<'a>(domain: Domain<'a>, r#type: QType, rdata: &'a [u8]) -> Answer<'a> {
+    Answer {
+        domain,
+        r#type,
+        class: QClass::IN,
+        ttl: 60,
+        rdata,
+    }
+}
+
+fn section(records: &[Record]) -> Vec<Answer<'_>> {
+    records
+        .iter()
+        .map(|(name, r#type, rdata)| record(Domain::String(name), *r#type, rdata))
+        .collect()
+}
+
+/// Encode a response to `query`, with `question` as its question.
+fn response(
+    query: &DnsMessage<'_>,
+    question: Question<'_>,
+    flags: Flags,
+    answers: &[Answer<'_>],
+    additionals: &[Answer<'_>],
+    rcode: Rcode,
+) -> Vec<u8> {
+    let mut buf = std::vec![0; 4096];
+    let len = DnsMessage {
+        id: query.id,
+        opcode: query.opcode,
+        flags,
+        rcode,
+        questions: Questions::Slice(&[question]),
+        answers: Answers::Slice(answers),
+        authorities: Answers::Slice(&[]),
+        additionals: Answers::Slice(additionals),
+        edns: query.edns,
+    }
+    .encode(&mut buf)
+    .unwrap();
+    buf.truncate(len);
+    buf
+}
+
+/// A clock where time only moves forward by waiting. Waiting lets other tasks
+/// run first, so that whatever is due earlier happens first.
+#[derive(Clone, Default)]
+pub(crate) struct MockClock {
+    now: Rc<Cell<u64>>,
+}
+
+impl MockClock {
+    async fn until(&self, at: u64) {
+        // Give other futures a chance to complete before moving time
+        loop {
+            yield_now().await;
+            if self.now.get() >= at {
+                return;
+            }
+            // The earliest waiter moves the clock. With several waiters, each one
+            // yielding first means the one due first sees its time first.
+            let now = self.now.get();
+            if now < at {
+                self.now.set(now + 1);
+            }
+        }
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        self.now.get()
+    }
+
+    async fn delay_ms(&self, ms: u32) {
+        self.until(self.now.get() + ms as u64).await
+    }
+}
+
+async fn yield_now() {
+    let mut yielded = false;
+    core::future::poll_fn(|cx| {
+        if yielded {
+            core::task::Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            core::task::Poll::Pending
+        }
+    })
+    .await
+}
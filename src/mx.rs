@@ -0,0 +1,19 @@
+use embedded_nal_async::IpAddr;
+use heapless::Vec;
+
+use crate::{DomainName, MAX_ADDRS};
+
+/// Maximum number of exchanges returned by
+/// [`ItsDns::lookup_mx`](crate::ItsDns::lookup_mx).
+pub const MAX_MX: usize = 8;
+
+/// A mail exchange of a domain, with its addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailExchange {
+    /// Exchanges with a lower preference are tried first.
+    pub preference: u16,
+    /// The host accepting mail for the domain.
+    pub exchange: DomainName,
+    /// The addresses of the exchange, empty if they could not be found.
+    pub addrs: Vec<IpAddr, MAX_ADDRS>,
+}
@@ -0,0 +1,258 @@
+use crate::message::{Domain, Labels, MAX_LABEL_LEN, MAX_NAME_LEN};
+use crate::{DnsError, DomainError};
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+use heapless::Vec;
+
+/// An owned domain name, stored in uncompressed wire format.
+///
+/// Unlike [`Domain`], a `DomainName` does not borrow the string or the message
+/// it was created from, so it can be kept after the packet buffer is reused.
+#[derive(Clone)]
+pub struct DomainName {
+    data: Vec<u8, MAX_NAME_LEN>,
+}
+
+impl DomainName {
+    /// The root name.
+    pub fn root() -> Self {
+        let mut data = Vec::new();
+        data.push(0).unwrap();
+        Self { data }
+    }
+
+    /// Create a name from a borrowed domain name, validating it in the process.
+    pub fn from_domain(domain: &Domain<'_>) -> Result<Self, DomainError> {
+        let mut buf = [0; MAX_NAME_LEN];
+        let len = domain.encode(&mut buf).map_err(|e| match e {
+            DnsError::InvalidDomain(e) => e,
+            _ => DomainError::NameTooLong,
+        })?;
+        Ok(Self {
+            data: Vec::from_slice(&buf[..len]).map_err(|_| DomainError::NameTooLong)?,
+        })
+    }
+
+    /// Borrow this name as a [`Domain`].
+    pub fn as_domain(&self) -> Domain<'_> {
+        Domain::Raw {
+            data: &self.data,
+            message: &self.data,
+        }
+    }
+
+    /// The name in uncompressed wire format, including the root label.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Iterate over the labels of this name, not including the root label.
+    pub fn labels(&self) -> Labels<'_> {
+        self.as_domain().labels()
+    }
+
+    /// Number of labels in this name, not including the root label.
+    pub fn label_count(&self) -> usize {
+        self.labels().count()
+    }
+
+    /// Returns true if this is the root name.
+    pub fn is_root(&self) -> bool {
+        self.data.len() == 1
+    }
+
+    /// The name with the leftmost label removed, or `None` for the root.
+    pub fn parent(&self) -> Option<DomainName> {
+        if self.is_root() {
+            return None;
+        }
+        let skip = self.data[0] as usize + 1;
+        Some(Self {
+            data: Vec::from_slice(&self.data[skip..]).ok()?,
+        })
+    }
+
+    /// Returns true if this name is equal to or below `other`, comparing
+    /// ASCII case-insensitively.
+    pub fn is_subdomain_of(&self, other: &Domain<'_>) -> bool {
+        let count = self.label_count();
+        let other_count = other.labels().count();
+        if count < other_count {
+            return false;
+        }
+        let mut skip = 0;
+        for _ in 0..count - other_count {
+            skip += self.data[skip] as usize + 1;
+        }
+        let suffix = &self.data[skip..];
+        Domain::Raw {
+            data: suffix,
+            message: suffix,
+        } == *other
+    }
+
+    /// Append `suffix` to this name, for instance to qualify a name with a
+    /// search domain.
+    pub fn append(&mut self, suffix: &Domain<'_>) -> Result<(), DomainError> {
+        let suffix = DomainName::from_domain(suffix)?;
+        if self.data.len() + suffix.data.len() - 1 > MAX_NAME_LEN {
+            return Err(DomainError::NameTooLong);
+        }
+        self.data.pop();
+        self.data
+            .extend_from_slice(&suffix.data)
+            .map_err(|_| DomainError::NameTooLong)
+    }
+
+    /// Prepend a single label, given as raw octets, to this name.
+    pub fn prepend_label(&mut self, label: &[u8]) -> Result<(), DomainError> {
+        if label.is_empty() {
+            return Err(DomainError::EmptyLabel);
+        }
+        if label.len() > MAX_LABEL_LEN {
+            return Err(DomainError::LabelTooLong);
+        }
+        if self.data.len() + label.len() + 1 > MAX_NAME_LEN {
+            return Err(DomainError::NameTooLong);
+        }
+        let mut data = Vec::new();
+        // Capacity is checked above
+        data.push(label.len() as u8).ok();
+        data.extend_from_slice(label).ok();
+        data.extend_from_slice(&self.data).ok();
+        self.data = data;
+        Ok(())
+    }
+}
+
+impl Default for DomainName {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+impl FromStr for DomainName {
+    type Err = DomainError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_domain(&Domain::String(s))
+    }
+}
+
+impl TryFrom<&str> for DomainName {
+    type Error = DomainError;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl<'a> TryFrom<&Domain<'a>> for DomainName {
+    type Error = DomainError;
+    fn try_from(domain: &Domain<'a>) -> Result<Self, Self::Error> {
+        Self::from_domain(domain)
+    }
+}
+
+impl<'a> From<&'a DomainName> for Domain<'a> {
+    fn from(name: &'a DomainName) -> Self {
+        name.as_domain()
+    }
+}
+
+impl PartialEq for DomainName {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_domain() == other.as_domain()
+    }
+}
+
+impl Eq for DomainName {}
+
+impl<'a> PartialEq<Domain<'a>> for DomainName {
+    fn eq(&self, other: &Domain<'a>) -> bool {
+        self.as_domain() == *other
+    }
+}
+
+impl PartialOrd for DomainName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DomainName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_domain().cmp(&other.as_domain())
+    }
+}
+
+impl Hash for DomainName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_domain().hash(state)
+    }
+}
+
+impl fmt::Display for DomainName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_domain().fmt(f)
+    }
+}
+
+impl fmt::Debug for DomainName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DomainName(\"{}\")", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::string::ToString;
+
+    #[test]
+    fn test_domain_name() {
+        let name: DomainName = "www.Example.com.".parse().unwrap();
+        assert_eq!(b"\x03www\x07Example\x03com\x00", name.as_bytes());
+        assert_eq!(3, name.label_count());
+        assert_eq!("www.Example.com", name.to_string());
+        assert_eq!(name, Domain::String("WWW.example.COM"));
+
+        let parent = name.parent().unwrap();
+        assert_eq!("Example.com", parent.to_string());
+        assert!(name.is_subdomain_of(&Domain::String("example.com")));
+        assert!(name.is_subdomain_of(&parent.as_domain()));
+        assert!(name.is_subdomain_of(&Domain::String(".")));
+        assert!(!parent.is_subdomain_of(&name.as_domain()));
+        assert!(!name.is_subdomain_of(&Domain::String("ample.com")));
+
+        let root = parent.parent().unwrap().parent().unwrap();
+        assert!(root.is_root());
+        assert_eq!(".", root.to_string());
+        assert!(root.parent().is_none());
+
+        let mut name: DomainName = "broker".parse().unwrap();
+        name.append(&Domain::String("site1.example.net")).unwrap();
+        assert_eq!("broker.site1.example.net", name.to_string());
+
+        let escaped: DomainName = "a\\.b\\032c.com".parse().unwrap();
+        assert_eq!(2, escaped.label_count());
+        assert_eq!("a\\.b\\032c.com", escaped.to_string());
+    }
+
+    #[test]
+    fn test_domain_name_too_long() {
+        let label = [b'a'; 63];
+        let mut name = DomainName::root();
+        for _ in 0..3 {
+            name.prepend_label(&label).unwrap();
+        }
+        assert_eq!(
+            Err(DomainError::NameTooLong),
+            name.append(&Domain::String(core::str::from_utf8(&label).unwrap()))
+        );
+        name.append(&Domain::String(core::str::from_utf8(&label[..61]).unwrap()))
+            .unwrap();
+        assert_eq!(255, name.as_bytes().len());
+    }
+}
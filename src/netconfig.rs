@@ -0,0 +1,299 @@
+use embedded_nal_async::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use heapless::Vec;
+
+use crate::message::{Domain, Reader};
+use crate::name::DomainName;
+use crate::{DecodeError, DecodeReason, DnsError, MAX_SEARCH, MAX_SERVERS};
+
+/// DHCPv4 Domain Name Server option (RFC 2132).
+pub const DHCP_DNS_SERVERS: u8 = 6;
+/// DHCPv4 Domain Name option (RFC 2132).
+pub const DHCP_DOMAIN_NAME: u8 = 15;
+/// DHCPv4 Domain Search option (RFC 3397).
+pub const DHCP_DOMAIN_SEARCH: u8 = 119;
+/// IPv6 Router Advertisement Recursive DNS Server option (RFC 8106).
+pub const RA_RDNSS: u8 = 25;
+/// IPv6 Router Advertisement DNS Search List option (RFC 8106).
+pub const RA_DNSSL: u8 = 31;
+
+/// DNS servers and search domains learnt from DHCPv4 options or IPv6 router
+/// advertisements, to apply with [`ItsDns::apply`](crate::ItsDns::apply).
+///
+/// Options can be added from several sources, for instance DHCPv4 for the
+/// IPv4 servers and router advertisements for the IPv6 ones. Servers and
+/// domains beyond [`MAX_SERVERS`] and [`MAX_SEARCH`] are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkConfig {
+    servers: Vec<SocketAddr, MAX_SERVERS>,
+    search: Vec<DomainName, MAX_SEARCH>,
+    /// The search list is the DHCP domain name, to be replaced by a search list.
+    search_from_domain: bool,
+    lifetime: Option<u32>,
+}
+
+fn error(offset: usize, reason: DecodeReason) -> DecodeError {
+    DecodeError { offset, reason }
+}
+
+fn decode_error(e: DnsError) -> DecodeError {
+    match e {
+        DnsError::Decode(e) => e,
+        _ => error(0, DecodeReason::InvalidRecord),
+    }
+}
+
+impl NetworkConfig {
+    /// Create an empty configuration.
+    pub const fn new() -> Self {
+        Self {
+            servers: Vec::new(),
+            search: Vec::new(),
+            search_from_domain: false,
+            lifetime: None,
+        }
+    }
+
+    /// The servers learnt, in the order they were added.
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    /// The search domains learnt.
+    pub fn search(&self) -> &[DomainName] {
+        &self.search
+    }
+
+    /// The shortest lifetime in seconds of the router advertisement options
+    /// added, after which they should be refreshed.
+    pub fn lifetime(&self) -> Option<u32> {
+        self.lifetime
+    }
+
+    fn add_server(&mut self, ip: IpAddr) {
+        let server = SocketAddr::new(ip, 53);
+        if !self.servers.contains(&server) {
+            self.servers.push(server).ok();
+        }
+    }
+
+    fn add_search(&mut self, name: DomainName) {
+        if !name.is_root() && !self.search.contains(&name) {
+            self.search.push(name).ok();
+        }
+    }
+
+    /// Add the payload of a DHCPv4 option, without the code and length octets.
+    ///
+    /// Options 6 (servers), 15 (domain name) and 119 (domain search) are used,
+    /// other options are ignored. The domain name is used as the search list,
+    /// unless there is a domain search option. An option that was split into
+    /// several parts (RFC 3396) must be concatenated first.
+    pub fn add_dhcp_option(&mut self, code: u8, payload: &[u8]) -> Result<(), DecodeError> {
+        match code {
+            DHCP_DNS_SERVERS => {
+                if payload.len() % 4 != 0 {
+                    return Err(error(payload.len() / 4 * 4, DecodeReason::UnexpectedEnd));
+                }
+                for ip in payload.chunks_exact(4) {
+                    self.add_server(IpAddr::V4(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])));
+                }
+            }
+            DHCP_DOMAIN_NAME => {
+                // Some servers include a terminating NUL
+                let name = payload.strip_suffix(&[0]).unwrap_or(payload);
+                let name = core::str::from_utf8(name)
+                    .ok()
+                    .and_then(|name| name.parse::<DomainName>().ok())
+                    .ok_or_else(|| error(0, DecodeReason::InvalidRecord))?;
+                if self.search.is_empty() {
+                    self.search_from_domain = true;
+                    self.add_search(name);
+                }
+            }
+            DHCP_DOMAIN_SEARCH => {
+                // Compression pointers are relative to the start of the option data
+                let names = decode_names(payload, payload)?;
+                if self.search_from_domain {
+                    self.search.clear();
+                    self.search_from_domain = false;
+                }
+                for name in names {
+                    self.add_search(name);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Add an IPv6 router advertisement option, given as the data following the
+    /// type and length octets.
+    ///
+    /// Options 25 (RDNSS) and 31 (DNSSL) are used, other options are ignored, as
+    /// are options with a lifetime of 0, which withdraw earlier ones.
+    pub fn add_ra_option(&mut self, r#type: u8, data: &[u8]) -> Result<(), DecodeError> {
+        if r#type != RA_RDNSS && r#type != RA_DNSSL {
+            return Ok(());
+        }
+        // 2 reserved octets and the lifetime
+        let mut r = Reader::new(data, data);
+        r.u16().map_err(decode_error)?;
+        let lifetime = r.u32().map_err(decode_error)?;
+        let data = &data[6..];
+
+        let mut servers: Vec<Ipv6Addr, MAX_SERVERS> = Vec::new();
+        let mut names = Vec::new();
+        if r#type == RA_RDNSS {
+            if data.is_empty() || data.len() % 16 != 0 {
+                return Err(error(6 + data.len() / 16 * 16, DecodeReason::UnexpectedEnd));
+            }
+            for ip in data.chunks_exact(16) {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(ip);
+                servers.push(Ipv6Addr::from(octets)).ok();
+            }
+        } else {
+            // The names are not compressed, and followed by zero padding
+            names = decode_names(data, data).map_err(|e| error(6 + e.offset, e.reason))?;
+        }
+
+        if lifetime == 0 {
+            return Ok(());
+        }
+        self.lifetime = Some(self.lifetime.map_or(lifetime, |l| l.min(lifetime)));
+        for ip in servers {
+            self.add_server(IpAddr::V6(ip));
+        }
+        if !names.is_empty() && self.search_from_domain {
+            self.search.clear();
+            self.search_from_domain = false;
+        }
+        for name in names {
+            self.add_search(name);
+        }
+        Ok(())
+    }
+
+    /// Add all DNS options from the options area of an IPv6 router advertisement,
+    /// made up of type, length and data.
+    pub fn add_ra_options(&mut self, options: &[u8]) -> Result<(), DecodeError> {
+        let mut pos = 0;
+        while pos < options.len() {
+            let len = *options
+                .get(pos + 1)
+                .ok_or_else(|| error(pos + 1, DecodeReason::UnexpectedEnd))?
+                as usize
+                * 8;
+            if len == 0 {
+                return Err(error(pos + 1, DecodeReason::InvalidRecord));
+            }
+            let option = options
+                .get(pos..pos + len)
+                .ok_or_else(|| error(pos, DecodeReason::UnexpectedEnd))?;
+            self.add_ra_option(option[0], &option[2..])
+                .map_err(|e| error(pos + 2 + e.offset, e.reason))?;
+            pos += len;
+        }
+        Ok(())
+    }
+}
+
+/// Decode a sequence of names, filling up `data`, which is a part of `message`.
+fn decode_names(data: &[u8], message: &[u8]) -> Result<Vec<DomainName, MAX_SEARCH>, DecodeError> {
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let (len, name) = Domain::decode(&data[pos..], message).map_err(decode_error)?;
+        pos += len;
+        let name =
+            DomainName::from_domain(&name).map_err(|_| error(pos, DecodeReason::NameTooLong))?;
+        if !name.is_root() {
+            names.push(name).ok();
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::string::ToString;
+
+    #[test]
+    fn test_dhcp_options() {
+        let mut config = NetworkConfig::new();
+        config
+            .add_dhcp_option(DHCP_DNS_SERVERS, &[192, 168, 1, 1, 8, 8, 8, 8])
+            .unwrap();
+        config
+            .add_dhcp_option(DHCP_DOMAIN_NAME, b"site1.example.net\0")
+            .unwrap();
+        assert_eq!(
+            [
+                "192.168.1.1:53".parse::<SocketAddr>().unwrap(),
+                "8.8.8.8:53".parse().unwrap()
+            ],
+            config.servers()
+        );
+        assert_eq!(1, config.search().len());
+        assert_eq!("site1.example.net", config.search()[0].to_string());
+
+        // RFC 3397 example, with a pointer to example.com
+        let search = b"\x03eng\x05apple\x03com\x00\x09marketing\xc0\x04";
+        config.add_dhcp_option(DHCP_DOMAIN_SEARCH, search).unwrap();
+        assert_eq!(2, config.search().len());
+        assert_eq!("eng.apple.com", config.search()[0].to_string());
+        assert_eq!("marketing.apple.com", config.search()[1].to_string());
+
+        config.add_dhcp_option(42, &[1, 2]).unwrap();
+        assert_eq!(
+            Err(error(4, DecodeReason::UnexpectedEnd)),
+            config.add_dhcp_option(DHCP_DNS_SERVERS, &[1, 2, 3, 4, 5])
+        );
+        assert_eq!(
+            Err(error(0, DecodeReason::InvalidPointer(0))),
+            config.add_dhcp_option(DHCP_DOMAIN_SEARCH, b"\xc0\x00")
+        );
+    }
+
+    #[test]
+    fn test_ra_options() {
+        let mut options = std::vec::Vec::new();
+        // RDNSS with one server, lifetime 600
+        options.extend_from_slice(&[RA_RDNSS, 3, 0, 0, 0, 0, 2, 0x58]);
+        options.extend_from_slice(&[
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x53,
+        ]);
+        // Prefix information, ignored
+        options.extend_from_slice(&[3, 1, 0, 0, 0, 0, 0, 0]);
+        // DNSSL with one name and padding, lifetime 300
+        options.extend_from_slice(&[RA_DNSSL, 4, 0, 0, 0, 0, 1, 0x2c]);
+        options.extend_from_slice(b"\x05site1\x07example\x03net\x00\x00\x00\x00\x00\x00");
+
+        let mut config = NetworkConfig::new();
+        config.add_ra_options(&options).unwrap();
+        assert_eq!(
+            ["[2001:db8::53]:53".parse::<SocketAddr>().unwrap()],
+            config.servers()
+        );
+        assert_eq!(1, config.search().len());
+        assert_eq!("site1.example.net", config.search()[0].to_string());
+        assert_eq!(Some(300), config.lifetime());
+
+        // Withdrawn
+        options[4..8].copy_from_slice(&[0; 4]);
+        let mut withdrawn = NetworkConfig::new();
+        withdrawn.add_ra_option(RA_RDNSS, &options[2..24]).unwrap();
+        assert!(withdrawn.servers().is_empty());
+
+        assert_eq!(
+            Err(error(2 + 6, DecodeReason::UnexpectedEnd)),
+            config.add_ra_options(&[RA_RDNSS, 1, 0, 0, 0, 0, 1, 0])
+        );
+        assert_eq!(
+            Err(error(1, DecodeReason::InvalidRecord)),
+            config.add_ra_options(&[RA_RDNSS, 0])
+        );
+    }
+}
@@ -0,0 +1,17 @@
+use embedded_nal_async::IpAddr;
+use heapless::Vec;
+
+use crate::{DomainName, MAX_ADDRS};
+
+/// Maximum number of name servers returned by
+/// [`ItsDns::lookup_ns`](crate::ItsDns::lookup_ns).
+pub const MAX_NS: usize = 8;
+
+/// An authoritative name server of a zone, with its glue addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameServer {
+    /// The name of the server.
+    pub name: DomainName,
+    /// The addresses of the server in the additional section, if any.
+    pub addrs: Vec<IpAddr, MAX_ADDRS>,
+}
@@ -0,0 +1,371 @@
+use core::fmt::Write;
+use core::marker::PhantomData;
+use core::str::FromStr;
+use embedded_nal_async::SocketAddr;
+
+use crate::message::{Answer, AnswerIter, Answers, DnsMessage, Domain, Reader};
+use crate::{DecodeReason, DnsError, DomainError, DomainName, QType, PACKET_LEN};
+
+/// Typed data of a record, decoded without copying from the message.
+pub trait RecordData<'a>: Sized {
+    /// The type of records with this data.
+    const TYPE: QType;
+
+    /// Decode the data of `answer`, which is a record of `message`. Names in the
+    /// data may be compressed, pointing elsewhere in the message.
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError>;
+}
+
+/// A buffer to receive a response into, which the records from
+/// [`ItsDns::lookup`](crate::ItsDns::lookup) borrow from.
+pub struct Packet(pub(crate) [u8; PACKET_LEN]);
+
+impl Packet {
+    /// Create an empty buffer.
+    pub const fn new() -> Self {
+        Self([0; PACKET_LEN])
+    }
+}
+
+impl Default for Packet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The records of a name with data of type `T` in a response, decoded as they
+/// are iterated.
+pub struct Records<'p, T> {
+    name: DomainName,
+    server: SocketAddr,
+    message: &'p [u8],
+    answers: AnswerIter<'p>,
+    authorities: Answers<'p>,
+    additionals: Answers<'p>,
+    authentic_data: bool,
+    data: PhantomData<T>,
+}
+
+impl<'p, T: RecordData<'p>> Records<'p, T> {
+    pub(crate) fn new(
+        name: DomainName,
+        server: SocketAddr,
+        message: &'p [u8],
+        m: &DnsMessage<'p>,
+    ) -> Self {
+        Self {
+            name,
+            server,
+            message,
+            answers: m.answers.iter(),
+            authorities: m.authorities,
+            additionals: m.additionals,
+            authentic_data: m.flags.authentic_data,
+            data: PhantomData,
+        }
+    }
+
+    /// The name of the records.
+    pub fn name(&self) -> &DomainName {
+        &self.name
+    }
+
+    /// The server that answered.
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    /// Returns true if the server set the AD bit, saying that it validated the
+    /// records with DNSSEC. This can only be trusted if the server is, and the
+    /// path to it is secure.
+    pub fn authentic_data(&self) -> bool {
+        self.authentic_data
+    }
+
+    /// The authority section of the response.
+    pub fn authorities(&self) -> AnswerIter<'p> {
+        self.authorities.iter()
+    }
+
+    /// The additional section of the response, which may have the addresses of
+    /// names in the records.
+    pub fn additionals(&self) -> AnswerIter<'p> {
+        self.additionals.iter()
+    }
+}
+
+impl<'p, T: RecordData<'p>> Iterator for Records<'p, T> {
+    type Item = Result<T, DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let answer = match self.answers.next()? {
+                Ok(answer) => answer,
+                Err(e) => return Some(Err(e)),
+            };
+            if self.name == answer.domain && answer.r#type == T::TYPE {
+                return Some(T::decode(&answer, self.message));
+            }
+        }
+    }
+}
+
+/// Check that the whole record data was decoded.
+fn finish(r: &Reader<'_>) -> Result<(), DnsError> {
+    match r.remaining() {
+        0 => Ok(()),
+        _ => Err(r.error(DecodeReason::InvalidRecord)),
+    }
+}
+
+/// The location of a service (RFC 2782).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Srv<'a> {
+    /// Targets with a lower priority are tried first.
+    pub priority: u16,
+    /// Relative weight among targets of the same priority.
+    pub weight: u16,
+    /// The port the service is on.
+    pub port: u16,
+    /// The host providing the service, or the root if the service is not
+    /// available.
+    pub target: Domain<'a>,
+}
+
+impl<'a> RecordData<'a> for Srv<'a> {
+    const TYPE: QType = QType::SRV;
+
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError> {
+        let mut r = Reader::new(answer.rdata, message);
+        let srv = Srv {
+            priority: r.u16()?,
+            weight: r.u16()?,
+            port: r.u16()?,
+            target: r.name()?,
+        };
+        finish(&r)?;
+        Ok(srv)
+    }
+}
+
+/// A mail exchange for a domain (RFC 1035).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mx<'a> {
+    /// Exchanges with a lower preference are tried first.
+    pub preference: u16,
+    /// The host accepting mail for the domain, or the root if the domain
+    /// accepts no mail (RFC 7505).
+    pub exchange: Domain<'a>,
+}
+
+impl<'a> RecordData<'a> for Mx<'a> {
+    const TYPE: QType = QType::MX;
+
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError> {
+        let mut r = Reader::new(answer.rdata, message);
+        let mx = Mx {
+            preference: r.u16()?,
+            exchange: r.name()?,
+        };
+        finish(&r)?;
+        Ok(mx)
+    }
+}
+
+/// An authoritative name server of a zone (RFC 1035).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ns<'a> {
+    /// The name of the server.
+    pub nsdname: Domain<'a>,
+}
+
+impl<'a> RecordData<'a> for Ns<'a> {
+    const TYPE: QType = QType::NS;
+
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError> {
+        let mut r = Reader::new(answer.rdata, message);
+        let ns = Ns { nsdname: r.name()? };
+        finish(&r)?;
+        Ok(ns)
+    }
+}
+
+/// The start of a zone of authority (RFC 1035).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Soa<'a> {
+    /// The primary name server of the zone.
+    pub mname: Domain<'a>,
+    /// The mailbox of the person responsible for the zone, with the first
+    /// label as the local part.
+    pub rname: Domain<'a>,
+    /// The version of the zone.
+    pub serial: u32,
+    /// Seconds before secondary servers refresh the zone.
+    pub refresh: u32,
+    /// Seconds before a failed refresh is retried.
+    pub retry: u32,
+    /// Seconds after which secondary servers stop answering for the zone if it
+    /// can not be refreshed.
+    pub expire: u32,
+    /// Seconds to cache negative answers for (RFC 2308).
+    pub minimum: u32,
+}
+
+impl<'a> RecordData<'a> for Soa<'a> {
+    const TYPE: QType = QType::SOA;
+
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError> {
+        let mut r = Reader::new(answer.rdata, message);
+        let soa = Soa {
+            mname: r.name()?,
+            rname: r.name()?,
+            serial: r.u32()?,
+            refresh: r.u32()?,
+            retry: r.u32()?,
+            expire: r.u32()?,
+            minimum: r.u32()?,
+        };
+        finish(&r)?;
+        Ok(soa)
+    }
+}
+
+/// A TLS certificate association for DANE (RFC 6698).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tlsa<'a> {
+    /// How the association is used: 0 for a CA and 1 for the certificate,
+    /// both also validated with PKIX, 2 for a trust anchor and 3 for the
+    /// certificate of the server.
+    pub usage: u8,
+    /// What is matched: 0 for the full certificate, 1 for its public key.
+    pub selector: u8,
+    /// How it is matched: 0 exactly, 1 by a SHA-256 hash, 2 by a SHA-512 hash.
+    pub matching_type: u8,
+    /// The certificate data, or its hash.
+    pub data: &'a [u8],
+}
+
+impl<'a> Tlsa<'a> {
+    /// The name of the TLSA records for a service on `port` over `protocol`,
+    /// such as `tcp`, at `host`, as in `_443._tcp.example.com`.
+    pub fn owner_name(port: u16, protocol: &str, host: &str) -> Result<DomainName, DomainError> {
+        let mut name = DomainName::from_str(host)?;
+        let mut label = heapless::String::<64>::new();
+        write!(label, "_{}", protocol).map_err(|_| DomainError::LabelTooLong)?;
+        name.prepend_label(label.as_bytes())?;
+        label.clear();
+        // A port has at most 5 digits
+        write!(label, "_{}", port).ok();
+        name.prepend_label(label.as_bytes())?;
+        Ok(name)
+    }
+}
+
+impl<'a> RecordData<'a> for Tlsa<'a> {
+    const TYPE: QType = QType::TLSA;
+
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError> {
+        let mut r = Reader::new(answer.rdata, message);
+        Ok(Tlsa {
+            usage: r.u8()?,
+            selector: r.u8()?,
+            matching_type: r.u8()?,
+            data: r.slice(r.remaining())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DecodeError, QClass};
+
+    fn answer(message: &[u8], r#type: QType, rdata: core::ops::Range<usize>) -> Answer<'_> {
+        Answer {
+            domain: Domain::String("example.net"),
+            r#type,
+            class: QClass::IN,
+            ttl: 60,
+            rdata: &message[rdata],
+        }
+    }
+
+    #[test]
+    fn test_srv() {
+        // The target points to example.net at offset 1
+        let message = b"\x00\x07example\x03net\x00\x00\x0a\x00\x05\x22\xb3\x06broker\xc0\x01\xff";
+        let srv = Srv::decode(&answer(message, QType::SRV, 14..29), message).unwrap();
+        assert_eq!(10, srv.priority);
+        assert_eq!(5, srv.weight);
+        assert_eq!(8883, srv.port);
+        assert_eq!(Domain::String("broker.example.net"), srv.target);
+
+        assert_eq!(
+            Err(DnsError::Decode(DecodeError {
+                offset: 29,
+                reason: DecodeReason::InvalidRecord
+            })),
+            Srv::decode(&answer(message, QType::SRV, 14..30), message)
+        );
+        assert_eq!(
+            Err(DnsError::Decode(DecodeError {
+                offset: 18,
+                reason: DecodeReason::UnexpectedEnd
+            })),
+            Srv::decode(&answer(message, QType::SRV, 14..19), message)
+        );
+    }
+
+    #[test]
+    fn test_mx() {
+        let message = b"\x00\x07example\x03net\x00\x00\x0a\x04mail\xc0\x01";
+        let mx = Mx::decode(&answer(message, QType::MX, 14..23), message).unwrap();
+        assert_eq!(10, mx.preference);
+        assert_eq!(Domain::String("mail.example.net"), mx.exchange);
+
+        let message = b"\x00\x00\x00";
+        let mx = Mx::decode(&answer(message, QType::MX, 0..3), message).unwrap();
+        assert_eq!(Domain::String("."), mx.exchange);
+    }
+
+    #[test]
+    fn test_soa() {
+        let mut message =
+            b"\x00\x07example\x03net\x00\x03ns1\xc0\x01\x0ahostmaster\xc0\x01".to_vec();
+        for n in [2024010101u32, 7200, 900, 1209600, 300] {
+            message.extend_from_slice(&n.to_be_bytes());
+        }
+        let soa = Soa::decode(&answer(&message, QType::SOA, 14..53), &message).unwrap();
+        assert_eq!(Domain::String("ns1.example.net"), soa.mname);
+        assert_eq!(Domain::String("hostmaster.example.net"), soa.rname);
+        assert_eq!(
+            [2024010101, 7200, 900, 1209600, 300],
+            [soa.serial, soa.refresh, soa.retry, soa.expire, soa.minimum]
+        );
+        let result = Soa::decode(&answer(&message, QType::SOA, 14..52), &message);
+        assert!(result.is_err());
+
+        let ns = Ns::decode(&answer(&message, QType::NS, 14..20), &message).unwrap();
+        assert_eq!(Domain::String("ns1.example.net"), ns.nsdname);
+    }
+
+    #[test]
+    fn test_tlsa() {
+        let message = b"\x03\x01\x01\xab\xcd";
+        let tlsa = Tlsa::decode(&answer(message, QType::TLSA, 0..5), message).unwrap();
+        assert_eq!((3, 1, 1), (tlsa.usage, tlsa.selector, tlsa.matching_type));
+        assert_eq!(b"\xab\xcd", tlsa.data);
+        assert!(Tlsa::decode(&answer(message, QType::TLSA, 0..2), message).is_err());
+
+        let name = Tlsa::owner_name(443, "tcp", "example.com").unwrap();
+        assert_eq!(Domain::String("_443._tcp.example.com"), name.as_domain());
+        let name = Tlsa::owner_name(8883, "udp", "example.com.").unwrap();
+        assert_eq!(Domain::String("_8883._udp.example.com"), name.as_domain());
+        let protocol = [b'a'; 63];
+        let protocol = core::str::from_utf8(&protocol).unwrap();
+        assert_eq!(
+            Err(DomainError::LabelTooLong),
+            Tlsa::owner_name(443, protocol, "example.com")
+        );
+    }
+}
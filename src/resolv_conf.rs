@@ -0,0 +1,174 @@
+use core::str::FromStr;
+use embedded_nal_async::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use heapless::Vec;
+
+use crate::name::DomainName;
+use crate::{Config, MAX_SEARCH, MAX_SERVERS, MAX_UDP_PAYLOAD};
+
+/// Settings from a resolv.conf file, as read by [`ResolvConf::parse`].
+///
+/// Parsing is lenient: unknown directives and options, and values that can not
+/// be parsed, are ignored, as are servers and search domains beyond
+/// [`MAX_SERVERS`] and [`MAX_SEARCH`].
+#[derive(Debug, Clone)]
+pub struct ResolvConf<'a> {
+    servers: Vec<SocketAddr, MAX_SERVERS>,
+    search: Vec<&'a str, MAX_SEARCH>,
+    ndots: Option<u8>,
+    timeout_ms: Option<u32>,
+    attempts: Option<u8>,
+    edns0: bool,
+}
+
+impl<'a> ResolvConf<'a> {
+    /// Parse the contents of a resolv.conf file.
+    ///
+    /// The `nameserver`, `search` and `domain` directives and the `ndots`,
+    /// `timeout`, `attempts` and `edns0` options are used. As with the C
+    /// library, the last `search` or `domain` directive wins, and a file
+    /// without name servers means the local one at `127.0.0.1`.
+    pub fn parse(text: &'a str) -> Self {
+        let mut conf = Self {
+            servers: Vec::new(),
+            search: Vec::new(),
+            ndots: None,
+            timeout_ms: None,
+            attempts: None,
+            edns0: false,
+        };
+        for line in text.lines() {
+            let line = match line.find(|c| c == '#' || c == ';') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+            let mut words = line.split_ascii_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    if let Some(ip) = words.next().and_then(parse_server) {
+                        conf.servers.push(SocketAddr::new(ip, 53)).ok();
+                    }
+                }
+                Some("search") | Some("domain") => {
+                    conf.search.clear();
+                    for domain in words {
+                        if domain != "." && DomainName::from_str(domain).is_ok() {
+                            conf.search.push(domain).ok();
+                        }
+                    }
+                }
+                Some("options") => {
+                    for option in words {
+                        conf.option(option);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if conf.servers.is_empty() {
+            let local = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53);
+            conf.servers.push(local).ok();
+        }
+        conf
+    }
+
+    fn option(&mut self, option: &str) {
+        let (name, value) = match option.split_once(':') {
+            Some((name, value)) => (name, value.parse::<u32>().ok()),
+            None => (option, None),
+        };
+        // Limits as in resolv.conf(5)
+        match (name, value) {
+            ("ndots", Some(n)) => self.ndots = Some(n.min(15) as u8),
+            ("timeout", Some(n)) => self.timeout_ms = Some(n.clamp(1, 30) * 1000),
+            ("attempts", Some(n)) => self.attempts = Some(n.clamp(1, 5) as u8),
+            ("edns0", _) => self.edns0 = true,
+            _ => {}
+        }
+    }
+
+    /// The name servers.
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    /// The search domains.
+    pub fn search(&self) -> &[&'a str] {
+        &self.search
+    }
+
+    /// A client configuration with these settings, and defaults for the rest.
+    pub fn config(&self) -> Config<'_> {
+        let mut config = Config::new().servers(&self.servers).search(&self.search);
+        if let Some(ndots) = self.ndots {
+            config = config.ndots(ndots);
+        }
+        if let Some(timeout_ms) = self.timeout_ms {
+            config = config.timeout_ms(timeout_ms);
+        }
+        if let Some(attempts) = self.attempts {
+            config = config.attempts(attempts);
+        }
+        if self.edns0 {
+            config = config.edns_payload_size(Some(MAX_UDP_PAYLOAD));
+        }
+        config
+    }
+}
+
+/// Parse a name server address, which may have a zone index that is ignored.
+fn parse_server(s: &str) -> Option<IpAddr> {
+    if let Ok(ip) = Ipv4Addr::from_str(s) {
+        return Some(IpAddr::V4(ip));
+    }
+    let s = s.split_once('%').map_or(s, |(ip, _)| ip);
+    Ipv6Addr::from_str(s).ok().map(IpAddr::V6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolv_conf() {
+        let conf = ResolvConf::parse(
+            "# Generated by NetworkManager\n\
+             domain example.com\n\
+             search site1.example.net  example.net ; trailing comment\n\
+             nameserver 192.168.1.1\n\
+             nameserver fe80::1%eth0\n\
+             nameserver not-an-address\n\
+             sortlist 130.155.160.0/255.255.240.0\n\
+             options ndots:2 timeout:1 attempts:9 edns0 rotate\n",
+        );
+        assert_eq!(
+            [
+                "192.168.1.1:53".parse::<SocketAddr>().unwrap(),
+                "[fe80::1]:53".parse().unwrap()
+            ],
+            conf.servers()
+        );
+        assert_eq!(["site1.example.net", "example.net"], conf.search());
+
+        let config = conf.config();
+        assert_eq!(2, config.ndots);
+        assert_eq!(1000, config.timeout_ms);
+        assert_eq!(5, config.attempts);
+        assert_eq!(Some(MAX_UDP_PAYLOAD), config.edns_payload_size);
+    }
+
+    #[test]
+    fn test_resolv_conf_defaults() {
+        let conf = ResolvConf::parse("search .\noptions ndots:x\n");
+        assert_eq!(
+            ["127.0.0.1:53".parse::<SocketAddr>().unwrap()],
+            conf.servers()
+        );
+        assert!(conf.search().is_empty());
+
+        let config = conf.config();
+        let default = Config::new();
+        assert_eq!(default.ndots, config.ndots);
+        assert_eq!(default.timeout_ms, config.timeout_ms);
+        assert_eq!(None, config.edns_payload_size);
+    }
+}
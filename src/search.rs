@@ -0,0 +1,123 @@
+use crate::message::strip_root;
+use crate::name::DomainName;
+
+/// Maximum number of search domains.
+pub const MAX_SEARCH: usize = 6;
+
+/// Default number of dots in a name for it to be tried as is before the search
+/// domains, as in resolv.conf.
+pub const DEFAULT_NDOTS: u8 = 1;
+
+/// The names to try for a host name, in order, following the resolv.conf rules.
+///
+/// A name ending with a dot is absolute and only tried as is. Otherwise a name
+/// with at least `ndots` dots is tried as is before the search domains, and a
+/// name with fewer after them. Names that would become too long with a search
+/// domain appended are skipped.
+pub(crate) struct Candidates<'a> {
+    name: DomainName,
+    search: &'a [DomainName],
+    as_is_first: bool,
+    next: usize,
+}
+
+impl<'a> Candidates<'a> {
+    pub(crate) fn new(host: &str, name: DomainName, search: &'a [DomainName], ndots: u8) -> Self {
+        let absolute = strip_root(host).len() < host.len();
+        let search = if absolute || name.is_root() {
+            &[]
+        } else {
+            search
+        };
+        let dots = name.label_count().saturating_sub(1);
+        Self {
+            as_is_first: dots >= ndots as usize,
+            name,
+            search,
+            next: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Candidates<'a> {
+    type Item = DomainName;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let step = self.next;
+            if step > self.search.len() {
+                return None;
+            }
+            self.next += 1;
+
+            let suffix = if self.as_is_first {
+                step.checked_sub(1)
+            } else {
+                Some(step).filter(|i| *i < self.search.len())
+            };
+            match suffix {
+                None => return Some(self.name.clone()),
+                Some(i) => {
+                    let mut name = self.name.clone();
+                    if name.append(&self.search[i].as_domain()).is_ok() {
+                        return Some(name);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    fn candidates(host: &str, search: &[DomainName], ndots: u8) -> Vec<String> {
+        Candidates::new(host, host.parse().unwrap(), search, ndots)
+            .map(|n| n.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_candidates() {
+        let search: [DomainName; 2] = [
+            "site1.example.net".parse().unwrap(),
+            "example.net".parse().unwrap(),
+        ];
+
+        assert_eq!(
+            ["broker.site1.example.net", "broker.example.net", "broker"],
+            candidates("broker", &search, 1)[..]
+        );
+        assert_eq!(
+            [
+                "broker.eu",
+                "broker.eu.site1.example.net",
+                "broker.eu.example.net"
+            ],
+            candidates("broker.eu", &search, 1)[..]
+        );
+        assert_eq!(
+            [
+                "broker.eu.site1.example.net",
+                "broker.eu.example.net",
+                "broker.eu"
+            ],
+            candidates("broker.eu", &search, 2)[..]
+        );
+        assert_eq!(["broker"], candidates("broker.", &search, 1)[..]);
+        assert_eq!(["broker"], candidates("broker", &[], 1)[..]);
+        // An escaped backslash before the dot leaves the dot unescaped
+        assert_eq!(["a\\\\"], candidates("a\\\\.", &search, 1)[..]);
+        assert_eq!(3, candidates("a\\.", &search, 1).len());
+
+        let a = "a".repeat(63);
+        let long: [DomainName; 1] = [std::format!("{0}.{0}.{0}.{1}", a, &a[..55])
+            .parse()
+            .unwrap()];
+        assert_eq!(["broker.eu"], candidates("broker.eu", &long, 1)[..]);
+    }
+}
@@ -0,0 +1,126 @@
+use embedded_nal_async::IpAddr;
+use heapless::Vec;
+
+use crate::{sort_stable_by_key, DomainName, MAX_ADDRS};
+
+/// Maximum number of targets returned by
+/// [`ItsDns::lookup_srv`](crate::ItsDns::lookup_srv).
+pub const MAX_SRV: usize = 8;
+
+/// A target of a service, with its addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvTarget {
+    /// Targets with a lower priority are tried first.
+    pub priority: u16,
+    /// Relative weight among targets of the same priority.
+    pub weight: u16,
+    /// The port the service is on.
+    pub port: u16,
+    /// The host providing the service.
+    pub target: DomainName,
+    /// The addresses of the target, empty if they could not be found.
+    pub addrs: Vec<IpAddr, MAX_ADDRS>,
+}
+
+/// Order targets as in RFC 2782: by priority, and within a priority by repeatedly
+/// picking one of the remaining targets at random, in proportion to its weight.
+/// Targets with a weight of 0 have a small chance of being picked first.
+pub(crate) fn order(targets: &mut [SrvTarget], mut random: impl FnMut() -> u16) {
+    sort_stable_by_key(targets, |t| t.priority);
+
+    let mut start = 0;
+    while start < targets.len() {
+        let priority = targets[start].priority;
+        let end = targets[start..]
+            .iter()
+            .position(|t| t.priority != priority)
+            .map_or(targets.len(), |n| start + n);
+        for i in start..end {
+            let remaining = &mut targets[i..end];
+            // Zero weights go first, so that they are picked when the random
+            // number is 0
+            let mut zeros = 0;
+            for k in 0..remaining.len() {
+                if remaining[k].weight == 0 {
+                    remaining[zeros..=k].rotate_right(1);
+                    zeros += 1;
+                }
+            }
+            let sum: u32 = remaining.iter().map(|t| t.weight as u32).sum();
+            let r = (((random() as u32) << 16) | random() as u32) % (sum + 1);
+            let mut running = 0;
+            let pick = remaining
+                .iter()
+                .position(|t| {
+                    running += t.weight as u32;
+                    running >= r
+                })
+                .unwrap_or(0);
+            remaining[..=pick].rotate_right(1);
+        }
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    fn target(priority: u16, weight: u16, name: &str) -> SrvTarget {
+        SrvTarget {
+            priority,
+            weight,
+            port: 443,
+            target: DomainName::from_str(name).unwrap(),
+            addrs: Vec::new(),
+        }
+    }
+
+    fn names(targets: &[SrvTarget]) -> [&[u8]; 4] {
+        let mut names = [&b""[..]; 4];
+        for (name, t) in names.iter_mut().zip(targets) {
+            // The first label
+            *name = &t.target.as_bytes()[1..2];
+        }
+        names
+    }
+
+    #[test]
+    fn test_order() {
+        let targets = [
+            target(20, 0, "d"),
+            target(10, 0, "a"),
+            target(10, 10, "b"),
+            target(10, 30, "c"),
+        ];
+        let ordered = |random: u32| {
+            let mut t = targets.clone();
+            order(&mut t, || random as u16);
+            t
+        };
+
+        // The random number is r * 0x10001 % (sum of the weights + 1)
+        assert_eq!([b"a", b"b", b"c", b"d"], names(&ordered(0)));
+        // 10 of 41 for b, then 21 of 31 for c
+        assert_eq!([b"b", b"c", b"a", b"d"], names(&ordered(7)));
+        // 12 of 41 for c, then 0 of 11 for a
+        assert_eq!([b"c", b"a", b"b", b"d"], names(&ordered(33)));
+
+        // Over many picks, each target comes first in proportion to its weight
+        let mut seed = 1u32;
+        let mut first = [0; 3];
+        for _ in 0..4000 {
+            let mut t = targets.clone();
+            order(&mut t, || {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u16
+            });
+            assert_eq!(b"d", names(&t)[3]);
+            first[(names(&t)[0][0] - b'a') as usize] += 1;
+        }
+        assert!(first[0] < 300, "{:?}", first);
+        assert!((800..1200).contains(&first[1]), "{:?}", first);
+        assert!((2700..3200).contains(&first[2]), "{:?}", first);
+    }
+}
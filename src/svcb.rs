@@ -0,0 +1,423 @@
+use core::ops::Deref;
+use core::slice::ChunksExact;
+use embedded_nal_async::{Ipv4Addr, Ipv6Addr};
+
+use crate::message::{Answer, Domain, Reader};
+use crate::{DecodeReason, DnsError, QType, RecordData, TxtStrings};
+
+/// Maximum number of aliases followed by
+/// [`ItsDns::lookup_https`](crate::ItsDns::lookup_https).
+pub const MAX_ALIASES: usize = 8;
+
+/// A service binding from an SVCB record (RFC 9460), such as `_dns.example.net`
+/// or `_8443._foo.example.net`.
+///
+/// The parameters were checked when decoding, so iterating over them can not
+/// fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Svcb<'a> {
+    /// The priority of the binding, lower values first, or 0 for an alias.
+    pub priority: u16,
+    /// The target name. For an alias, the name to lookup instead, and `.` if
+    /// the service is not available. For a service, the host providing it, and
+    /// `.` for the owner name of the record.
+    pub target: Domain<'a>,
+    params: &'a [u8],
+}
+
+/// A service binding from an HTTPS record (RFC 9460), which has the same data
+/// as an SVCB record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Https<'a>(pub Svcb<'a>);
+
+impl<'a> Deref for Https<'a> {
+    type Target = Svcb<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a> RecordData<'a> for Svcb<'a> {
+    const TYPE: QType = QType::SVCB;
+
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError> {
+        let mut r = Reader::new(answer.rdata, message);
+        let priority = r.u16()?;
+        let target = r.name()?;
+        let params = &answer.rdata[answer.rdata.len() - r.remaining()..];
+        let mut last = None;
+        while r.remaining() > 0 {
+            let start = answer.rdata.len() - r.remaining();
+            let key = r.u16()?;
+            let len = r.u16()?;
+            let value = r.slice(len as usize)?;
+            // Keys must be in increasing order, which also rules out duplicates
+            if last.map_or(false, |last| key <= last) || !valid_param(key, value) {
+                return Err(r.error_at(start, DecodeReason::InvalidRecord));
+            }
+            last = Some(key);
+        }
+        Ok(Svcb {
+            priority,
+            target,
+            params,
+        })
+    }
+}
+
+impl<'a> RecordData<'a> for Https<'a> {
+    const TYPE: QType = QType::HTTPS;
+
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError> {
+        Svcb::decode(answer, message).map(Https)
+    }
+}
+
+/// Check the value of a parameter with a known key.
+fn valid_param(key: u16, value: &[u8]) -> bool {
+    match key {
+        SvcParam::MANDATORY => !value.is_empty() && value.len() % 2 == 0,
+        SvcParam::ALPN => {
+            let mut len = 0;
+            // Each id takes its length and one octet or more
+            for id in TxtStrings::new(value) {
+                if id.is_empty() {
+                    return false;
+                }
+                len += 1 + id.len();
+            }
+            len > 0 && len == value.len()
+        }
+        SvcParam::NO_DEFAULT_ALPN => value.is_empty(),
+        SvcParam::PORT => value.len() == 2,
+        SvcParam::IPV4HINT => !value.is_empty() && value.len() % 4 == 0,
+        SvcParam::IPV6HINT => !value.is_empty() && value.len() % 16 == 0,
+        _ => true,
+    }
+}
+
+impl<'a> Svcb<'a> {
+    /// Returns true if this is an alias to another name (AliasMode), rather
+    /// than a binding to a service (ServiceMode).
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+
+    /// The parameters of the binding, in the order of their keys.
+    pub fn params(&self) -> SvcParams<'a> {
+        SvcParams { data: self.params }
+    }
+
+    /// The application protocols supported, such as `h2` or `h3`, in addition
+    /// to the default one unless [`no_default_alpn`](Self::no_default_alpn).
+    pub fn alpn(&self) -> Option<AlpnIds<'a>> {
+        self.params().find_map(|p| match p {
+            SvcParam::Alpn(ids) => Some(ids),
+            _ => None,
+        })
+    }
+
+    /// Returns true if the default application protocol is not supported.
+    pub fn no_default_alpn(&self) -> bool {
+        self.params().any(|p| matches!(p, SvcParam::NoDefaultAlpn))
+    }
+
+    /// The port of the service, if not the default one.
+    pub fn port(&self) -> Option<u16> {
+        self.params().find_map(|p| match p {
+            SvcParam::Port(port) => Some(port),
+            _ => None,
+        })
+    }
+
+    /// IPv4 addresses of the target, which may be used before they are looked
+    /// up.
+    pub fn ipv4_hints(&self) -> Option<Ipv4Hints<'a>> {
+        self.params().find_map(|p| match p {
+            SvcParam::Ipv4Hint(hints) => Some(hints),
+            _ => None,
+        })
+    }
+
+    /// IPv6 addresses of the target, which may be used before they are looked
+    /// up.
+    pub fn ipv6_hints(&self) -> Option<Ipv6Hints<'a>> {
+        self.params().find_map(|p| match p {
+            SvcParam::Ipv6Hint(hints) => Some(hints),
+            _ => None,
+        })
+    }
+
+    /// The Encrypted ClientHello configuration list.
+    pub fn ech(&self) -> Option<&'a [u8]> {
+        self.params().find_map(|p| match p {
+            SvcParam::Ech(config) => Some(config),
+            _ => None,
+        })
+    }
+}
+
+/// A parameter of a service binding, borrowing its value from the message.
+#[derive(Debug, Clone)]
+pub enum SvcParam<'a> {
+    /// Keys of the parameters a client must understand to use the binding.
+    Mandatory(SvcParamKeys<'a>),
+    /// Application protocol ids.
+    Alpn(AlpnIds<'a>),
+    /// The default application protocol is not supported.
+    NoDefaultAlpn,
+    /// The port of the service.
+    Port(u16),
+    /// IPv4 addresses of the target.
+    Ipv4Hint(Ipv4Hints<'a>),
+    /// An Encrypted ClientHello configuration list.
+    Ech(&'a [u8]),
+    /// IPv6 addresses of the target.
+    Ipv6Hint(Ipv6Hints<'a>),
+    /// A parameter with another key, and its value.
+    Unknown(u16, &'a [u8]),
+}
+
+impl<'a> SvcParam<'a> {
+    /// The key of [`SvcParam::Mandatory`].
+    pub const MANDATORY: u16 = 0;
+    /// The key of [`SvcParam::Alpn`].
+    pub const ALPN: u16 = 1;
+    /// The key of [`SvcParam::NoDefaultAlpn`].
+    pub const NO_DEFAULT_ALPN: u16 = 2;
+    /// The key of [`SvcParam::Port`].
+    pub const PORT: u16 = 3;
+    /// The key of [`SvcParam::Ipv4Hint`].
+    pub const IPV4HINT: u16 = 4;
+    /// The key of [`SvcParam::Ech`].
+    pub const ECH: u16 = 5;
+    /// The key of [`SvcParam::Ipv6Hint`].
+    pub const IPV6HINT: u16 = 6;
+
+    /// The key of the parameter.
+    pub fn key(&self) -> u16 {
+        match self {
+            Self::Mandatory(_) => Self::MANDATORY,
+            Self::Alpn(_) => Self::ALPN,
+            Self::NoDefaultAlpn => Self::NO_DEFAULT_ALPN,
+            Self::Port(_) => Self::PORT,
+            Self::Ipv4Hint(_) => Self::IPV4HINT,
+            Self::Ech(_) => Self::ECH,
+            Self::Ipv6Hint(_) => Self::IPV6HINT,
+            Self::Unknown(key, _) => *key,
+        }
+    }
+}
+
+/// Iterator over the parameters of a service binding.
+#[derive(Debug, Clone)]
+pub struct SvcParams<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for SvcParams<'a> {
+    type Item = SvcParam<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The lengths and values were checked when decoding
+        let (header, rest) = (self.data.get(..4)?, &self.data[4..]);
+        let key = u16::from_be_bytes([header[0], header[1]]);
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let value = rest.get(..len)?;
+        self.data = &rest[len..];
+        Some(match key {
+            SvcParam::MANDATORY => SvcParam::Mandatory(SvcParamKeys {
+                keys: value.chunks_exact(2),
+            }),
+            SvcParam::ALPN => SvcParam::Alpn(AlpnIds {
+                ids: TxtStrings::new(value),
+            }),
+            SvcParam::NO_DEFAULT_ALPN => SvcParam::NoDefaultAlpn,
+            SvcParam::PORT => SvcParam::Port(u16::from_be_bytes([value[0], value[1]])),
+            SvcParam::IPV4HINT => SvcParam::Ipv4Hint(Ipv4Hints {
+                addrs: value.chunks_exact(4),
+            }),
+            SvcParam::ECH => SvcParam::Ech(value),
+            SvcParam::IPV6HINT => SvcParam::Ipv6Hint(Ipv6Hints {
+                addrs: value.chunks_exact(16),
+            }),
+            key => SvcParam::Unknown(key, value),
+        })
+    }
+}
+
+/// Iterator over the keys of a [`SvcParam::Mandatory`] parameter.
+#[derive(Debug, Clone)]
+pub struct SvcParamKeys<'a> {
+    keys: ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for SvcParamKeys<'a> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.keys.next().map(|k| u16::from_be_bytes([k[0], k[1]]))
+    }
+}
+
+/// Iterator over the ids of a [`SvcParam::Alpn`] parameter.
+#[derive(Debug, Clone)]
+pub struct AlpnIds<'a> {
+    ids: TxtStrings<'a>,
+}
+
+impl<'a> Iterator for AlpnIds<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ids.next()
+    }
+}
+
+/// Iterator over the addresses of a [`SvcParam::Ipv4Hint`] parameter.
+#[derive(Debug, Clone)]
+pub struct Ipv4Hints<'a> {
+    addrs: ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for Ipv4Hints<'a> {
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.addrs
+            .next()
+            .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+    }
+}
+
+/// Iterator over the addresses of a [`SvcParam::Ipv6Hint`] parameter.
+#[derive(Debug, Clone)]
+pub struct Ipv6Hints<'a> {
+    addrs: ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for Ipv6Hints<'a> {
+    type Item = Ipv6Addr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.addrs.next().map(|a| {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(a);
+            Ipv6Addr::from(octets)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DecodeError, QClass};
+    extern crate std;
+    use std::vec::Vec;
+
+    fn https(rdata: &[u8]) -> Result<Https<'_>, DnsError> {
+        let answer = Answer {
+            domain: Domain::String("example.com"),
+            r#type: QType::HTTPS,
+            class: QClass::IN,
+            ttl: 60,
+            rdata,
+        };
+        Https::decode(&answer, rdata)
+    }
+
+    fn param(key: u16, value: &[u8]) -> Vec<u8> {
+        let mut param = key.to_be_bytes().to_vec();
+        param.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        param.extend_from_slice(value);
+        param
+    }
+
+    fn v6_octets() -> [u8; 16] {
+        [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+    }
+
+    #[test]
+    fn test_svcb() {
+        let rdata = [
+            &b"\x00\x01\x03svc\x07example\x03com\x00"[..],
+            &param(SvcParam::MANDATORY, &[0, 1, 0, 3]),
+            &param(SvcParam::ALPN, b"\x02h2\x02h3"),
+            &param(SvcParam::NO_DEFAULT_ALPN, b""),
+            &param(SvcParam::PORT, &[0x20, 0xfb]),
+            &param(SvcParam::IPV4HINT, &[192, 0, 2, 1, 192, 0, 2, 2]),
+            &param(SvcParam::ECH, b"config"),
+            &param(SvcParam::IPV6HINT, &v6_octets()),
+            &param(65000, b"x"),
+        ]
+        .concat();
+        let record = https(&rdata).unwrap();
+        assert!(!record.is_alias());
+        assert_eq!(1, record.priority);
+        assert_eq!(Domain::String("svc.example.com"), record.target);
+
+        let keys: Vec<u16> = record.params().map(|p| p.key()).collect();
+        assert_eq!([0, 1, 2, 3, 4, 5, 6, 65000], keys[..]);
+        match record.params().next() {
+            Some(SvcParam::Mandatory(keys)) => assert_eq!([1, 3], keys.collect::<Vec<_>>()[..]),
+            _ => panic!("no mandatory keys"),
+        }
+        let alpn: Vec<&[u8]> = record.alpn().unwrap().collect();
+        assert_eq!([b"h2", b"h3"], alpn[..]);
+        assert!(record.no_default_alpn());
+        assert_eq!(Some(8443), record.port());
+        let v4: Vec<Ipv4Addr> = record.ipv4_hints().unwrap().collect();
+        assert_eq!(
+            [Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)],
+            v4[..]
+        );
+        let v6: Vec<Ipv6Addr> = record.ipv6_hints().unwrap().collect();
+        assert_eq!([Ipv6Addr::from(v6_octets())], v6[..]);
+        assert_eq!(Some(&b"config"[..]), record.ech());
+        assert!(matches!(
+            record.params().last(),
+            Some(SvcParam::Unknown(65000, b"x"))
+        ));
+
+        let alias = https(b"\x00\x00\x03cdn\x07example\x03net\x00").unwrap();
+        assert!(alias.is_alias());
+        assert_eq!(None, alias.params().next().map(|p| p.key()));
+        assert_eq!(None, alias.port());
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let invalid = |params: &[&[u8]]| {
+            let rdata = [&b"\x00\x01\x00"[..], &params.concat()].concat();
+            https(&rdata)
+                == Err(DnsError::Decode(DecodeError {
+                    offset: 3 + params[..params.len() - 1]
+                        .iter()
+                        .map(|p| p.len())
+                        .sum::<usize>(),
+                    reason: DecodeReason::InvalidRecord,
+                }))
+        };
+        assert!(invalid(&[&param(SvcParam::PORT, &[1])]));
+        assert!(invalid(&[&param(SvcParam::ALPN, b"")]));
+        assert!(invalid(&[&param(SvcParam::ALPN, b"\x02h2\x00")]));
+        assert!(invalid(&[&param(SvcParam::ALPN, b"\x03h2")]));
+        assert!(invalid(&[&param(SvcParam::NO_DEFAULT_ALPN, b"x")]));
+        assert!(invalid(&[&param(SvcParam::IPV4HINT, &[192, 0, 2])]));
+        assert!(invalid(&[&param(SvcParam::IPV6HINT, &[0; 15])]));
+        assert!(invalid(&[&param(SvcParam::MANDATORY, &[0])]));
+        // Keys out of order, or repeated
+        assert!(invalid(&[
+            &param(SvcParam::PORT, &[0, 1]),
+            &param(SvcParam::ALPN, b"\x02h2")
+        ]));
+        assert!(invalid(&[
+            &param(SvcParam::PORT, &[0, 1]),
+            &param(SvcParam::PORT, &[0, 2])
+        ]));
+
+        // A value cut short
+        assert!(https(b"\x00\x01\x00\x00\x03\x00\x02\x01").is_err());
+    }
+}
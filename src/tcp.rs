@@ -0,0 +1,60 @@
+use embedded_io::asynch::{Read, Write};
+use embedded_io::ErrorKind;
+use embedded_nal_async::{SocketAddr, TcpConnect};
+
+/// A TCP stack for clients without one, failing every connection, so that
+/// truncated responses are never retried over TCP.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoTcp;
+
+/// A connection of [`NoTcp`], of which there are none.
+#[derive(Debug)]
+pub enum NoConnection {}
+
+impl embedded_io::Io for NoConnection {
+    type Error = ErrorKind;
+}
+
+impl Read for NoConnection {
+    async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        match *self {}
+    }
+}
+
+impl Write for NoConnection {
+    async fn write(&mut self, _buf: &[u8]) -> Result<usize, ErrorKind> {
+        match *self {}
+    }
+}
+
+impl TcpConnect for NoTcp {
+    type Error = ErrorKind;
+    type Connection<'a> = NoConnection where Self: 'a;
+
+    async fn connect<'a>(&'a self, _remote: SocketAddr) -> Result<NoConnection, ErrorKind>
+    where
+        Self: 'a,
+    {
+        Err(ErrorKind::Other)
+    }
+}
+
+/// Send the query in `packet[..len]` to `server` over TCP, and receive the
+/// response into `packet`, both prefixed with their length (RFC 1035 section
+/// 4.2.2). Returns the length of the response, or `None` if the exchange failed
+/// or the response does not fit in `packet`.
+pub(crate) async fn exchange<T: TcpConnect>(
+    tcp: &T,
+    server: SocketAddr,
+    packet: &mut [u8],
+    len: usize,
+) -> Option<usize> {
+    let mut conn = tcp.connect(server).await.ok()?;
+    conn.write_all(&(len as u16).to_be_bytes()).await.ok()?;
+    conn.write_all(&packet[..len]).await.ok()?;
+    let mut prefix = [0; 2];
+    conn.read_exact(&mut prefix).await.ok()?;
+    let response = packet.get_mut(..u16::from_be_bytes(prefix) as usize)?;
+    conn.read_exact(response).await.ok()?;
+    Some(response.len())
+}
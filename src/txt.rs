@@ -0,0 +1,246 @@
+use crate::message::{Answer, Reader};
+use crate::{DnsError, QType, RecordData};
+
+/// Text strings (RFC 1035), such as the `key=value` [`Attribute`]s of a service
+/// (RFC 6763) or a host (RFC 1464).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Txt<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RecordData<'a> for Txt<'a> {
+    const TYPE: QType = QType::TXT;
+
+    fn decode(answer: &Answer<'a>, message: &'a [u8]) -> Result<Self, DnsError> {
+        let mut r = Reader::new(answer.rdata, message);
+        while r.remaining() > 0 {
+            let len = r.u8()?;
+            r.slice(len as usize)?;
+        }
+        Ok(Txt { data: answer.rdata })
+    }
+}
+
+impl<'a> Txt<'a> {
+    /// The character-strings of the record, in order.
+    pub fn strings(&self) -> TxtStrings<'a> {
+        TxtStrings::new(self.data)
+    }
+
+    /// Total length of the strings.
+    pub fn len(&self) -> usize {
+        self.strings().map(<[u8]>::len).sum()
+    }
+
+    /// Returns true if all strings are empty, or there are none.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The strings joined into one value, for values longer than the 255 octets
+    /// of a string. A single string is returned as is, and several strings are
+    /// copied into `buf`, giving `None` if it is too small.
+    pub fn value<'b>(&self, buf: &'b mut [u8]) -> Option<&'b [u8]>
+    where
+        'a: 'b,
+    {
+        let mut strings = self.strings();
+        match (strings.next(), strings.next()) {
+            (None, _) => Some(&[]),
+            (Some(s), None) => Some(s),
+            _ => {
+                let len = self.len();
+                let mut pos = 0;
+                for s in self.strings() {
+                    buf.get_mut(pos..pos + s.len())?.copy_from_slice(s);
+                    pos += s.len();
+                }
+                Some(&buf[..len])
+            }
+        }
+    }
+
+    /// The strings parsed as attributes, one per string, skipping those without
+    /// a key.
+    pub fn attributes(&self) -> impl Iterator<Item = Attribute<'a>> {
+        self.strings().filter_map(Attribute::parse)
+    }
+}
+
+/// Iterator over the character-strings of a [`Txt`] record.
+#[derive(Debug, Clone)]
+pub struct TxtStrings<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> TxtStrings<'a> {
+    /// Iterate over character-strings whose lengths have been checked.
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+impl<'a> Iterator for TxtStrings<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&len, rest) = self.data.split_first()?;
+        // The lengths were checked when decoding
+        let s = rest.get(..len as usize)?;
+        self.data = &rest[s.len()..];
+        Some(s)
+    }
+}
+
+/// A `key=value` attribute in a text string (RFC 1464, RFC 6763).
+///
+/// The key ends at the first `=` and leading and trailing whitespace around it
+/// is ignored. A backquote escapes the character after it in the key, so that
+/// `` `= ``, ``` `` ``` and `` `  `` are part of the key. Everything after the
+/// `=` is the value, as is. A string without `=` has a key but no value, which
+/// RFC 6763 uses for boolean attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attribute<'a> {
+    key: &'a [u8],
+    value: Option<&'a [u8]>,
+}
+
+impl<'a> Attribute<'a> {
+    /// Parse a string into an attribute, or `None` if the key is empty.
+    pub fn parse(s: &'a [u8]) -> Option<Self> {
+        let mut start = None;
+        let mut end = 0;
+        let mut escaped = false;
+        let mut value = None;
+        for (i, &b) in s.iter().enumerate() {
+            match b {
+                _ if escaped => escaped = false,
+                b'`' => escaped = true,
+                b'=' => {
+                    value = Some(&s[i + 1..]);
+                    break;
+                }
+                b' ' | b'\t' => continue,
+                _ => {}
+            }
+            start.get_or_insert(i);
+            end = i + 1;
+        }
+        Some(Self {
+            key: &s[start?..end],
+            value,
+        })
+    }
+
+    /// The key with escapes removed.
+    pub fn key(&self) -> KeyBytes<'a> {
+        KeyBytes {
+            key: self.key.iter(),
+        }
+    }
+
+    /// The key as it is in the string, without the whitespace around it.
+    pub fn raw_key(&self) -> &'a [u8] {
+        self.key
+    }
+
+    /// Returns true if the key is `key`, ignoring ASCII case.
+    pub fn key_eq(&self, key: &str) -> bool {
+        let lower = |b: u8| b.to_ascii_lowercase();
+        self.key().map(lower).eq(key.bytes().map(lower))
+    }
+
+    /// The value after the `=`, or `None` if there is no `=`.
+    pub fn value(&self) -> Option<&'a [u8]> {
+        self.value
+    }
+}
+
+/// Iterator over the octets of an [`Attribute`] key, with escapes removed.
+#[derive(Debug, Clone)]
+pub struct KeyBytes<'a> {
+    key: core::slice::Iter<'a, u8>,
+}
+
+impl<'a> Iterator for KeyBytes<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.key.next()? {
+            // A backquote at the end of the string escapes nothing
+            b'`' => Some(*self.key.next().unwrap_or(&b'`')),
+            b => Some(*b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Domain;
+    use crate::{DecodeError, DecodeReason, QClass};
+    extern crate std;
+    use std::vec::Vec;
+
+    fn txt(rdata: &[u8]) -> Result<Txt<'_>, DnsError> {
+        let answer = Answer {
+            domain: Domain::String("example.com"),
+            r#type: QType::TXT,
+            class: QClass::IN,
+            ttl: 60,
+            rdata,
+        };
+        Txt::decode(&answer, rdata)
+    }
+
+    #[test]
+    fn test_txt() {
+        let record = txt(b"\x05hello\x00\x06 world").unwrap();
+        let strings: Vec<&[u8]> = record.strings().collect();
+        assert_eq!([&b"hello"[..], b"", b" world"], strings[..]);
+        assert_eq!(11, record.len());
+
+        let mut buf = [0; 11];
+        assert_eq!(Some(&b"hello world"[..]), record.value(&mut buf));
+        assert_eq!(None, record.value(&mut buf[..10]));
+        assert_eq!(
+            Some(&b"hello"[..]),
+            txt(b"\x05hello").unwrap().value(&mut [])
+        );
+        assert!(txt(b"").unwrap().is_empty());
+
+        assert_eq!(
+            Err(DnsError::Decode(DecodeError {
+                offset: 7,
+                reason: DecodeReason::UnexpectedEnd
+            })),
+            txt(b"\x05hello\x06 worl")
+        );
+    }
+
+    #[test]
+    fn test_attributes() {
+        let record = txt(b"\x0apath=/mqtt\x03tls\x05=oops\x06 Port=\x07a=b=c=d").unwrap();
+        let attributes: Vec<Attribute<'_>> = record.attributes().collect();
+        assert_eq!(4, attributes.len());
+        assert!(attributes[0].key_eq("PATH"));
+        assert_eq!(Some(&b"/mqtt"[..]), attributes[0].value());
+        assert!(attributes[1].key_eq("tls"));
+        assert_eq!(None, attributes[1].value());
+        assert!(attributes[2].key_eq("port"));
+        assert_eq!(Some(&b""[..]), attributes[2].value());
+        assert_eq!(Some(&b"b=c=d"[..]), attributes[3].value());
+
+        // Escaped equal signs, backquotes and whitespace are part of the key
+        let a = Attribute::parse(b" `=a`` b` =1 ").unwrap();
+        assert_eq!(b"`=a`` b` ", a.raw_key());
+        assert_eq!(b"=a` b ", &a.key().collect::<Vec<u8>>()[..]);
+        assert_eq!(Some(&b"1 "[..]), a.value());
+        assert!(Attribute::parse(b"\tkey\t=value").unwrap().key_eq("key"));
+        assert_eq!(
+            b"k`",
+            &Attribute::parse(b"k`").unwrap().key().collect::<Vec<u8>>()[..]
+        );
+        assert_eq!(None, Attribute::parse(b"  "));
+    }
+}